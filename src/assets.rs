@@ -0,0 +1,193 @@
+use crate::config::Config;
+use crate::error::{Result, ResultContext, TechouError};
+use crate::io_utils::{slurp, spit};
+use crate::utils;
+
+use std::collections::BTreeMap;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+/// Compile the `.scss` / `.sass` files and minify the `.css` and `.js` files in the
+/// asset folders (within the public folder) and write them with fingerprinted
+/// filenames (e.g. `css/style.3f2a1b.css`) into the output folder.
+/// Returns the output url for each source path (e.g. `css/style.scss`), which is
+/// used by the `asset_url` template function.
+pub fn process_assets(config: &Config) -> Result<BTreeMap<String, String>> {
+    let mut urls = BTreeMap::new();
+    if !config.assets.enable {
+        return Ok(urls);
+    }
+    let public_folder = config.folders.public_folder_path();
+    let output_folder = config.folders.output_folder_path();
+    for folder in &config.assets.folders {
+        let mut files = Vec::new();
+        asset_files(&public_folder.join(folder), &mut files)?;
+        for file in files {
+            let relative = match file.strip_prefix(&public_folder) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => continue,
+            };
+            let (contents, extension) = match process_file(&file, config)? {
+                Some(result) => result,
+                None => continue,
+            };
+            let stem = relative
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let filename = match config.assets.fingerprint {
+                true => format!(
+                    "{}.{}.{}",
+                    stem,
+                    utils::hash_string(&contents, 6),
+                    extension
+                ),
+                false => format!("{}.{}", stem, extension),
+            };
+            let output = relative.with_file_name(filename);
+            spit(output_folder.join(&output), &contents)?;
+            urls.insert(path_string(&relative), format!("/{}", path_string(&output)));
+        }
+    }
+    Ok(urls)
+}
+
+/// The processed contents and the extension of the output file. `None` for
+/// files that are not processed (e.g. Sass partials or images)
+fn process_file(file: &Path, config: &Config) -> Result<Option<(String, &'static str)>> {
+    let filename = file
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let extension = file
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let minify = config.assets.minify;
+    let processed = match extension {
+        // Partials are only imported
+        "scss" | "sass" if filename.starts_with('_') => None,
+        "scss" | "sass" => {
+            let style = match minify {
+                true => grass::OutputStyle::Compressed,
+                false => grass::OutputStyle::Expanded,
+            };
+            let mut options = grass::Options::default().style(style);
+            if let Some(parent) = file.parent() {
+                options = options.load_path(parent);
+            }
+            let css = grass::from_path(file, &options).map_err(|e| TechouError::Other {
+                issue: format!("{:?}: Could not compile Sass: {}", file, e),
+            })?;
+            Some((css, "css"))
+        }
+        "css" if minify && !filename.ends_with(".min.css") => {
+            let contents = slurp(file)?;
+            let css = minifier::css::minify(&contents).map_err(|e| TechouError::Other {
+                issue: format!("{:?}: Could not minify CSS: {}", file, e),
+            })?;
+            Some((css.to_string(), "css"))
+        }
+        "js" if minify && !filename.ends_with(".min.js") => {
+            let contents = slurp(file)?;
+            Some((minifier::js::minify(&contents).to_string(), "js"))
+        }
+        "css" => Some((slurp(file)?, "css")),
+        "js" => Some((slurp(file)?, "js")),
+        _ => None,
+    };
+    Ok(processed)
+}
+
+/// Is the file at `path` (within the public folder) compiled by `process_assets`?
+/// Those files are not copied into the output folder as they are.
+pub fn is_processed(path: &Path, config: &Config) -> bool {
+    if !config.assets.enable || path.is_dir() {
+        return false;
+    }
+    let public_folder = config.folders.public_folder_path();
+    let in_asset_folder = config
+        .assets
+        .folders
+        .iter()
+        .any(|folder| path.starts_with(public_folder.join(folder)));
+    let extension = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    in_asset_folder && matches!(extension, "scss" | "sass" | "css" | "js")
+}
+
+fn asset_files(directory: &Path, into: &mut Vec<PathBuf>) -> Result<()> {
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    for entry in read_dir(directory).ctx(directory)? {
+        let path = entry.ctx(directory)?.path();
+        if path.is_dir() {
+            asset_files(&path, into)?;
+        } else {
+            into.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Paths with `/` on all platforms, as they're used in urls
+fn path_string(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sass() {
+        use crate::assets::process_file;
+        use crate::config::Config;
+        let folder = std::env::temp_dir().join("techou-assets-test");
+        std::fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("style.scss");
+        std::fs::write(&file, "$color: red;\n.a { .b { color: $color; } }\n").unwrap();
+        let mut config = Config::default();
+        config.assets.minify = true;
+        let (css, extension) = process_file(&file, &config).unwrap().unwrap();
+        assert_eq!(extension, "css");
+        assert_eq!(css.trim(), ".a .b{color:red}");
+        std::fs::write(folder.join("_partial.scss"), "").unwrap();
+        assert!(process_file(&folder.join("_partial.scss"), &config)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_assets_and_copied_files() {
+        use crate::assets::{is_processed, process_assets};
+        use crate::config::Config;
+        use crate::io_utils::copy_items_to_directory;
+        let folder = std::env::temp_dir().join("techou-assets-copy-test");
+        let _ = std::fs::remove_dir_all(&folder);
+        let mut config = Config::default();
+        config.folders.root = folder.clone();
+        config.assets.enable = true;
+        config.assets.fingerprint = false;
+        let css = config.folders.public_folder_path().join("css");
+        std::fs::create_dir_all(&css).unwrap();
+        std::fs::write(css.join("style.scss"), ".a { .b { color: red; } }\n").unwrap();
+        std::fs::write(css.join("font.woff"), "font").unwrap();
+        process_assets(&config).unwrap();
+        copy_items_to_directory(
+            &config.folders.public_copy_folders,
+            &config.folders.public_folder_path(),
+            &config.folders.output_folder_path(),
+            |path| is_processed(path, &config),
+        )
+        .unwrap();
+        let output = config.folders.output_folder_path().join("css");
+        assert!(output.join("style.css").exists());
+        assert!(output.join("font.woff").exists());
+        assert!(!output.join("style.scss").exists());
+    }
+}
@@ -23,6 +23,12 @@ fn main() {
         )
         .arg(Arg::new("watch").short('w').long("watch").required(false))
         .arg(Arg::new("serve").short('s').long("serve").required(false))
+        .arg(
+            Arg::new("drafts")
+                .long("drafts")
+                .help("Also render unpublished and scheduled posts (marked as drafts)")
+                .required(false),
+        )
         .subcommand(
             App::new("new").about("Write a new post").arg(
                 Arg::new("filename")
@@ -41,6 +47,7 @@ fn main() {
                         .required(false),
                 ),
         )
+        .subcommand(App::new("check").about("Build and report broken internal links, e.g. for CI"))
         .subcommand(
             App::new("scaffold-book")
                 .about("Generate the folder structure and files for a book scaffolding")
@@ -111,11 +118,25 @@ fn main() {
     // If the server is on, the user is debugging, and we perform the auto reload
     config.server.auto_reload_browser_via_websocket_on_change = should_serve;
 
+    // Drafts are rendered for previews, but never end up in feeds, sitemap or search
+    if matches.is_present("drafts") {
+        config.project.render_drafts = true;
+    }
+
     if let Some(_matches) = matches.subcommand_matches("new") {
         techou::new_post::interactive(&config);
     }
 
     let cache = techou::build_cache::BuildCache::new("buildcache.techou");
+
+    // Build once and check the links in the output, e.g. for CI
+    if matches.subcommand_matches("check").is_some() {
+        let broken = match techou::executor::check(&config, &cache) {
+            Ok(broken) => broken,
+            Err(e) => panic!("Could not build project: {:?}", &e),
+        };
+        ::std::process::exit(if broken.is_empty() { 0 } else { 1 });
+    }
     let load_fn = move |path: &path::Path, config: &techou::config::Config| {
         let cache_clone = cache.clone();
         let watch_or_serve = should_watch || should_serve;
@@ -135,6 +156,17 @@ fn main() {
             config.folders.pages_folder_path(),
         ];
 
+        if config.folders.data_folder_path().exists() {
+            paths.push(config.folders.data_folder_path());
+        }
+
+        for collection in &config.collections {
+            let folder = collection.folder_path(&config.folders);
+            if folder.exists() {
+                paths.push(folder);
+            }
+        }
+
         if !config.folders.books.is_empty() {
             let book_root = PathBuf::from(&config.folders.books_folder);
             config.folders.books.iter().for_each(|book| {
@@ -1,14 +1,17 @@
+use lazy_static::*;
 use pulldown_cmark::{Event, Parser, Tag};
 use rayon::prelude::*;
+use regex::{Captures, Regex};
 use serde_derive::Serialize;
 
 use crate::config::Config;
-use crate::document::Document;
+use crate::document::{should_render, Document, Renderers, Section};
 use crate::error::Result;
 use crate::front_matter::*;
 use crate::io_utils::slurp;
-use crate::utils::DebugTimer;
-use std::path::PathBuf;
+use crate::utils::{DebugTimer, HeadingAnchors};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Debug)]
 pub struct Book {
@@ -24,6 +27,7 @@ impl Book {
     pub fn new<A: AsRef<std::path::Path>>(
         file: A,
         config: &Config,
+        renderers: &Renderers,
         cache: &crate::build_cache::BuildCache,
         force_update: bool,
     ) -> Result<Book> {
@@ -49,9 +53,15 @@ impl Book {
         let chapters: Vec<Chapter> = chapter_info
             .into_par_iter()
             .filter_map(|c| {
-                match c.convert(&base_folder_string, &config, &cache.clone(), force_update) {
-                    Ok(s) => {
-                        if s.document.info.published == false {
+                match c.convert(
+                    &base_folder_string,
+                    &config,
+                    renderers,
+                    &cache.clone(),
+                    force_update,
+                ) {
+                    Ok(mut s) => {
+                        if !should_render(&mut s.document, &config) {
                             return None;
                         }
                         Some(s)
@@ -112,15 +122,39 @@ impl Book {
         }
     }
 
+    /// The documents of all chapters, including the sub chapters
+    pub fn documents_mut(&mut self) -> Vec<&mut Document> {
+        let mut documents = Vec::new();
+        Book::documents_recursive(&mut self.chapters, &mut documents);
+        documents
+    }
+
+    fn documents_recursive<'a>(chapters: &'a mut [Chapter], into: &mut Vec<&'a mut Document>) {
+        for chapter in chapters {
+            into.push(&mut chapter.document);
+            Book::documents_recursive(&mut chapter.sub_chapters, into);
+        }
+    }
+
     /// Render the whole book (i.e. all chapters) as one one document
     /// This is currently a not-so-nice solution.
     /// It writes all the html together into one document with the
     /// frontMatter of the original document
     /// Then, it merges them with <h1> headlines
+    /// The anchors of each chapter are prefixed with its name, so that
+    /// the same heading in two chapters doesn't collide
     pub fn as_one_document(&self, cache: &crate::build_cache::BuildCache) -> Document {
         let mut buffer: String = String::new();
-        let mut sections: Vec<(String, String)> = Vec::new();
-        Book::recursive_add(&self.chapters, &mut buffer, &mut sections, None, 0);
+        let mut sections: Vec<Section> = Vec::new();
+        let mut prefixes = HeadingAnchors::default();
+        Book::recursive_add(
+            &self.chapters,
+            &mut buffer,
+            &mut sections,
+            &mut prefixes,
+            None,
+            0,
+        );
 
         let slug_path = PathBuf::from(&self.slug);
         let parent = slug_path.parent().expect("Expect a parent for a book");
@@ -132,8 +166,15 @@ impl Book {
         let doc = match clone.get_item(slug, &buffer) {
             Some(e) => e,
             None => {
-                let doc =
-                    Document::from_multiple(buffer, "", slug, &filename, &self.info, sections);
+                // The chapters are in the language of the book
+                let lang = self
+                    .chapters
+                    .first()
+                    .map(|chapter| chapter.document.lang.as_str())
+                    .unwrap_or_default();
+                let doc = Document::from_multiple(
+                    buffer, "", slug, &filename, &self.info, sections, lang,
+                );
                 cache.set_item(slug, &doc);
                 doc
             }
@@ -144,7 +185,8 @@ impl Book {
     fn recursive_add(
         chapters: &Vec<Chapter>,
         into_buffer: &mut String,
-        sections: &mut Vec<(String, String)>,
+        sections: &mut Vec<Section>,
+        prefixes: &mut HeadingAnchors,
         maximum_levels: Option<usize>,
         current_level: usize,
     ) {
@@ -154,14 +196,21 @@ impl Book {
             //into_buffer.push_str(&format!("<h1 id=\"header-section-{}\">{}</h1>", &counter, chapter.name));
             //sections.push((format!("header-section-{}", counter), chapter.name.clone()));
             //counter += 1;
-            into_buffer.push_str(&chapter.document.content);
+            let name = Path::new(&chapter.slug)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(&chapter.name);
+            let prefix = prefixes.anchor(name, None);
+            into_buffer.push_str(&prefix_anchors(&chapter.document.content, &prefix));
             let mut cloned = chapter.document.sections.clone();
+            prefix_sections(&mut cloned, &prefix);
             sections.append(&mut cloned);
             if !chapter.sub_chapters.is_empty() {
                 Book::recursive_add(
                     &chapter.sub_chapters,
                     into_buffer,
                     sections,
+                    prefixes,
                     maximum_levels,
                     current_level + 1,
                 );
@@ -170,6 +219,35 @@ impl Book {
     }
 }
 
+/// Prefix the ids in the html of a chapter and the references to them
+/// (`href="#id"`, `url(#id)` in svgs) with `prefix`
+fn prefix_anchors(html: &str, prefix: &str) -> String {
+    lazy_static! {
+        static ref IDS: Regex = Regex::new(r#"\sid="([^"]+)""#).unwrap();
+        static ref REFERENCES: Regex = Regex::new(r##"(\sid="|href="#|url\(#)([^")]+)"##).unwrap();
+    }
+    let ids: HashSet<&str> = IDS
+        .captures_iter(html)
+        .filter_map(|captures| captures.get(1))
+        .map(|id| id.as_str())
+        .collect();
+    REFERENCES
+        .replace_all(html, |captures: &Captures| {
+            match ids.contains(&captures[2]) {
+                true => format!("{}{}-{}", &captures[1], prefix, &captures[2]),
+                false => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+fn prefix_sections(sections: &mut [Section], prefix: &str) {
+    for section in sections {
+        section.anchor = format!("{}-{}", prefix, &section.anchor);
+        prefix_sections(&mut section.children, prefix);
+    }
+}
+
 #[derive(Serialize, Debug, Clone)]
 pub struct ChapterLink {
     pub name: String,
@@ -214,6 +292,7 @@ impl ChapterInfo {
         self,
         in_folder: &str,
         config: &Config,
+        renderers: &Renderers,
         cache: &crate::build_cache::BuildCache,
         force_update: bool,
     ) -> Result<Chapter> {
@@ -224,10 +303,18 @@ impl ChapterInfo {
         let mut doc = match clone.get_item(cache_key, &contents) {
             Some(mut e) => {
                 e.updated = force_update;
+                renderers.images.write_variants(&e.images);
                 e
             }
             None => {
-                let doc = Document::new(&contents, &self.file_url, "", &config, Some(in_folder))?;
+                let doc = Document::new(
+                    &contents,
+                    &self.file_url,
+                    "",
+                    &config,
+                    renderers,
+                    Some(in_folder),
+                )?;
                 cache.set_item(cache_key, &doc);
                 doc
             }
@@ -237,10 +324,10 @@ impl ChapterInfo {
         let chapters: Vec<Chapter> = self
             .sub_chapters
             .into_par_iter()
-            .filter_map(
-                |c| match c.convert(&in_folder, &config, &cache.clone(), force_update) {
-                    Ok(s) => {
-                        if s.document.info.published == false {
+            .filter_map(|c| {
+                match c.convert(&in_folder, &config, renderers, &cache.clone(), force_update) {
+                    Ok(mut s) => {
+                        if !should_render(&mut s.document, &config) {
                             return None;
                         }
                         Some(s)
@@ -249,8 +336,8 @@ impl ChapterInfo {
                         println!("{:?}", &e);
                         None
                     }
-                },
-            )
+                }
+            })
             .collect();
         Ok(Chapter {
             name: self.name,
@@ -405,6 +492,15 @@ pub fn parse_chapter<A: AsRef<std::path::Path>, B: AsRef<std::path::Path>>(
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_prefix_anchors() {
+        let html = r##"<h2 id="intro">Intro</h2><a href="#intro">a</a><a href="#other">b</a><svg><marker id="arrow"/><line marker-end="url(#arrow)"/></svg>"##;
+        assert_eq!(
+            prefix_anchors(html, "chapter1"),
+            r##"<h2 id="chapter1-intro">Intro</h2><a href="#chapter1-intro">a</a><a href="#other">b</a><svg><marker id="chapter1-arrow"/><line marker-end="url(#chapter1-arrow)"/></svg>"##
+        );
+    }
     #[test]
     fn test_parse_book_summary() {
         let content = r#"- [Intro](test1/test1.md)
@@ -1,3 +1,6 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
 use std::collections::HashMap;
 use std::error::Error;
 use std::path::Path;
@@ -9,29 +12,41 @@ use crate::io_utils::slurp;
 use crate::io_utils::spit;
 use crate::utils;
 
+/// The contents of the build cache file
+#[derive(Serialize, Deserialize, Default)]
+struct CacheFile {
+    documents: HashMap<String, (String, Document)>,
+    #[serde(default)]
+    artifacts: HashMap<String, serde_json::Value>,
+}
+
 /// Simple in-memory build-cache that
-/// Keeps the rendered markdown for a document
+/// Keeps the rendered markdown for a document and
+/// the information about generated artifacts (e.g. resized images)
 #[derive(Clone)]
 pub struct BuildCache {
     cache: Arc<Mutex<HashMap<String, (String, Document)>>>,
+    artifacts: Arc<Mutex<HashMap<String, serde_json::Value>>>,
     filename: PathBuf,
 }
 
 impl BuildCache {
     pub fn new<A: AsRef<Path>>(from: A) -> BuildCache {
-        if from.as_ref().exists() {
-            let data = slurp(from.as_ref()).unwrap();
-            let deserialized: HashMap<String, (String, Document)> =
-                serde_json::from_str(&data).unwrap();
-            BuildCache {
-                cache: Arc::new(Mutex::new(deserialized)),
-                filename: from.as_ref().to_owned(),
-            }
-        } else {
-            BuildCache {
-                cache: Arc::new(Mutex::new(HashMap::new())),
-                filename: from.as_ref().to_owned(),
+        let contents = match from.as_ref().exists() {
+            true => {
+                let data = slurp(from.as_ref()).unwrap();
+                // Older caches only contain the documents
+                serde_json::from_str::<CacheFile>(&data).unwrap_or_else(|_| CacheFile {
+                    documents: serde_json::from_str(&data).unwrap(),
+                    artifacts: HashMap::new(),
+                })
             }
+            false => CacheFile::default(),
+        };
+        BuildCache {
+            cache: Arc::new(Mutex::new(contents.documents)),
+            artifacts: Arc::new(Mutex::new(contents.artifacts)),
+            filename: from.as_ref().to_owned(),
         }
     }
 
@@ -53,9 +68,34 @@ impl BuildCache {
         return None;
     }
 
+    /// Store the information about a generated artifact. The `key` should
+    /// contain the hash of the contents the artifact was generated from
+    pub fn set_artifact<T: Serialize>(&self, key: &str, artifact: &T) {
+        if let Ok(value) = serde_json::to_value(artifact) {
+            self.artifacts
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value);
+        }
+    }
+
+    pub fn get_artifact<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let artifacts = self.artifacts.lock().unwrap();
+        let value = artifacts.get(key)?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Remove all documents, e.g. if a change invalidates the rendered documents
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
     pub fn write(&self) -> Result<(), Box<dyn Error>> {
         println!("Write Build Cache");
-        let data = self.cache.lock().unwrap().clone();
+        let data = CacheFile {
+            documents: self.cache.lock().unwrap().clone(),
+            artifacts: self.artifacts.lock().unwrap().clone(),
+        };
         let serialized = serde_json::to_string(&data).unwrap();
         spit(self.filename.as_path(), &serialized).unwrap();
         Ok(())
@@ -1,7 +1,8 @@
 use rayon::prelude::*;
+use serde::Serialize;
 
 use crate::book::{Book, Chapter};
-use crate::config::Config;
+use crate::config::{Config, ConfigCollection, ConfigTaxonomy};
 use crate::document::Document;
 use crate::error::Result;
 use crate::list::*;
@@ -90,33 +91,98 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
+    /// Write the documents of a collection with the collection template and,
+    /// if enabled, the paginated index pages of the collection
+    pub fn collection(&self, collection: &ConfigCollection, documents: &[Document]) -> Result<()> {
+        let folder = self.config.folders.output_folder_path();
+        let template = collection.template(&self.config.templates);
+        documents.par_iter().for_each(|document| {
+            if document.updated == false {
+                return;
+            }
+            let path = folder.nonAdjoinedPush(&document.slug);
+            match self.template_writer.write_document_with_template(
+                template,
+                &self.context,
+                &document,
+                &path,
+                &self.config,
+            ) {
+                Ok(_) => (),
+                Err(e) => println!("Could not write article {}: {:?}", &document.filename, &e),
+            }
+        });
+        if !collection.indexes || documents.iter().all(|d| d.updated == false) {
+            return Ok(());
+        }
+        let title_fn = |index| match index {
+            0 => ("index.html".to_string(), collection.name.clone()),
+            _ => (
+                format!("index-{}.html", index),
+                format!("{} - Page {}", &collection.name, index),
+            ),
+        };
+        self.write_paged(
+            documents,
+            self.config.project.posts_per_index as usize,
+            title_fn,
+            self.language_folder(collection.folder_name()),
+            ListType::Collection,
+            &self.config.templates.list_template,
+        )
+    }
+
     /// Write a number of posts as chunks into multiple index files.
     /// `make_title(i32) -> (String, String)` is a function that returns the
     /// filename and the title of an index page based on the index. 0 being the first
     /// `per_page` is the number of posts that should be on one page before a new one begins
-    pub fn indexes_paged<A: AsRef<Path>, TitleFn>(
+    pub fn indexes_paged<A: AsRef<Path>, TitleFn, D: AsRef<Document>>(
         &self,
-        posts: &[Document],
+        posts: &[D],
         per_page: usize,
         make_title: TitleFn,
         folder: A,
     ) -> Result<()>
     where
         TitleFn: Fn(usize) -> (String, String),
+        D: Serialize,
     {
-        if posts.iter().all(|d| d.updated == false) {
+        if posts.iter().all(|d| d.as_ref().updated == false) {
             return Ok(());
         }
+        self.write_paged(
+            posts,
+            per_page,
+            make_title,
+            folder,
+            ListType::Index,
+            &self.config.templates.list_template,
+        )
+    }
+
+    /// Write the paginated list pages with the given `template`.
+    /// See `indexes_paged` for the parameters
+    fn write_paged<A: AsRef<Path>, TitleFn, D: AsRef<Document>>(
+        &self,
+        posts: &[D],
+        per_page: usize,
+        make_title: TitleFn,
+        folder: A,
+        list_type: ListType,
+        template: &str,
+    ) -> Result<()>
+    where
+        TitleFn: Fn(usize) -> (String, String),
+        D: Serialize,
+    {
         let folder = self
             .config
             .folders
             .output_folder_path()
             .join(folder.as_ref());
         let mut state: (Option<Page>, Option<Page>) = (None, None);
-        let mut iter = posts.chunks(per_page).enumerate().peekable();
+        let mut iter = posts.chunks(per_page.max(1)).enumerate().peekable();
         while let Some((index, chunk)) = iter.next() {
-            println!("Index {index}. Chunk liength {}", chunk.len());
-            // dbg!(&index, chunk);
             let (filename, title) = make_title(index);
 
             let (_, future_title) = make_title(index + 1);
@@ -135,13 +201,14 @@ impl<'a> Builder<'a> {
 
             let path = folder.join(&filename);
 
-            match self.template_writer.write_list(
+            match self.template_writer.write_list_with_template(
+                template,
                 &self.context,
                 &List {
                     title: &title,
                     posts: chunk,
                     pagination,
-                    list_type: ListType::Index,
+                    list_type: list_type.clone(),
                 },
                 &path,
                 self.config,
@@ -159,70 +226,116 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
-    /// Write out documents for each category with the articles for that name
+    /// Write out paginated documents for each category with the articles for that name
     pub fn category<A: AsRef<Path>>(&self, tag_posts: &[Category<'a>], folder: A) -> Result<()> {
-        let folder = self
-            .config
-            .folders
-            .output_folder_path()
-            .join(folder.as_ref());
+        self.categories_paged(tag_posts, folder, &self.config.templates.list_template)
+    }
+
+    /// Write out the archives and the overview of a user-defined taxonomy
+    pub fn taxonomy(&self, taxonomy: &ConfigTaxonomy, tag_posts: &[Category<'a>]) -> Result<()> {
+        let folder = self.language_folder(taxonomy.folder_name());
+        self.categories_paged(
+            tag_posts,
+            &folder,
+            taxonomy.template(&self.config.templates),
+        )?;
+        self.overview(tag_posts, &folder, &taxonomy.name)
+    }
+
+    /// Translations are written into `/{lang}/folder`
+    fn language_folder(&self, folder: &str) -> String {
+        match self.config.languages.is_default(self.context.lang) {
+            true => folder.to_string(),
+            false => format!("{}/{}", self.context.lang, folder),
+        }
+    }
+
+    fn categories_paged<A: AsRef<Path>>(
+        &self,
+        tag_posts: &[Category<'a>],
+        folder: A,
+        template: &str,
+    ) -> Result<()> {
+        let per_page = self.config.project.posts_per_index as usize;
         for tag in tag_posts {
-            let slug = format!("{}.html", &utils::slugify(&tag.name));
-            let path = folder.join(&slug);
-            match self.template_writer.write_list(
-                &self.context,
-                &List {
-                    title: tag.name,
-                    posts: tag.posts.as_slice(),
-                    pagination: Pagination {
-                        current: 0,
-                        next: None,
-                        previous: None,
-                    },
-                    list_type: ListType::Category,
-                },
-                &path,
-                &self.config,
-            ) {
-                Ok(_) => (), /*println!("Wrote tag index: {:?}", &path)*/
-                Err(e) => println!("Could not write index {:?}: {:?}", &path, &e),
+            let slug = utils::slugify(&tag.name);
+            let title_fn = |index| match index {
+                0 => (format!("{}.html", &slug), tag.name.to_string()),
+                _ => (
+                    format!("{}-{}.html", &slug, index),
+                    format!("{} - Page {}", tag.name, index),
+                ),
             };
+            self.write_paged(
+                tag.posts.as_slice(),
+                per_page,
+                title_fn,
+                folder.as_ref(),
+                ListType::Category,
+                template,
+            )?;
         }
         Ok(())
     }
 
-    /// Write out documents for each category with the articles for that name
-    pub fn years<A: AsRef<Path>>(&self, year_posts: &[Year<'a>], folder: A) -> Result<()> {
-        let folder = self
+    /// Write out one overview page (`index.html`) that lists every category with
+    /// the number of articles for it
+    pub fn overview<A: AsRef<Path>>(
+        &self,
+        tag_posts: &[Category<'a>],
+        folder: A,
+        title: &str,
+    ) -> Result<()> {
+        let folder_name = folder.as_ref().to_str().unwrap_or_default();
+        let entries = tag_posts
+            .iter()
+            .map(|tag| OverviewEntry {
+                name: tag.name,
+                count: tag.count,
+                path: format!("/{}/{}.html", folder_name, &utils::slugify(&tag.name)),
+            })
+            .collect();
+        let path = self
             .config
             .folders
             .output_folder_path()
-            .join(folder.as_ref());
+            .join(folder.as_ref())
+            .join("index.html");
+        match self.template_writer.write_overview(
+            &self.context,
+            &Overview { title, entries },
+            &path,
+            &self.config,
+        ) {
+            Ok(_) => (),
+            Err(e) => println!("Could not write overview {:?}: {:?}", &path, &e),
+        };
+        Ok(())
+    }
+
+    /// Write out paginated documents for each year with the articles of that year
+    pub fn years<A: AsRef<Path>>(&self, year_posts: &[Year<'a>], folder: A) -> Result<()> {
+        let per_page = self.config.project.posts_per_index as usize;
         for year in year_posts {
-            let slug = format!("{}.html", &year.name);
-            let path = folder.join(&slug);
             let mut posts: Vec<&Document> = Vec::new();
             for x in year.months.iter() {
                 posts.extend(&x.posts);
             }
-            match self.template_writer.write_year(
-                &self.context,
-                &List {
-                    title: &format!("{}", year.name),
-                    posts: posts.as_slice(),
-                    pagination: Pagination {
-                        current: 0,
-                        next: None,
-                        previous: None,
-                    },
-                    list_type: ListType::Category,
-                },
-                &path,
-                self.config,
-            ) {
-                Ok(_) => (), /*println!("Wrote tag index: {:?}", &path)*/
-                Err(e) => println!("Could not write index {:?}: {:?}", &path, &e),
+            let title_fn = |index| match index {
+                0 => (format!("{}.html", &year.name), format!("{}", year.name)),
+                _ => (
+                    format!("{}-{}.html", &year.name, index),
+                    format!("{} - Page {}", year.name, index),
+                ),
             };
+            self.write_paged(
+                posts.as_slice(),
+                per_page,
+                title_fn,
+                folder.as_ref(),
+                ListType::Year,
+                &self.config.templates.year_template,
+            )?;
         }
         Ok(())
     }
@@ -1,3 +1,4 @@
+use pulldown_cmark::Options;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::error::*;
@@ -32,6 +33,10 @@ pub struct ConfigProject {
     // just the necessary stuff required to work on an article
     #[serde(default)]
     pub fast_render: bool,
+    // Render unpublished posts and posts scheduled for the future.
+    // They're marked as `draft` and left out of feeds, sitemap and search.
+    #[serde(default)]
+    pub render_drafts: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -40,15 +45,33 @@ pub struct ConfigRenderer {
     // Should code syntax be highlighted
     #[serde(default)]
     pub highlight_syntax: bool,
-    // Should custom syntax highlighting (via Splash) be used for Swift?
-    #[serde(default)]
-    pub swift_use_splash: bool,
     // Markdown table support
     #[serde(default)]
     pub markdown_tables: bool,
     // Markdown footnotes support
     #[serde(default)]
     pub markdown_footnotes: bool,
+    // Markdown ~~strikethrough~~ support
+    #[serde(default)]
+    pub markdown_strikethrough: bool,
+    // Markdown `- [x] task` list support
+    #[serde(default)]
+    pub markdown_tasklists: bool,
+    // Typographic quotes, dashes and ellipses
+    #[serde(default)]
+    pub markdown_smart_punctuation: bool,
+    // `# Heading {#custom-id .class}` support
+    #[serde(default)]
+    pub markdown_heading_attributes: bool,
+    // Render `$inline$`, `$$display$$` math and ```math blocks to MathML
+    #[serde(default)]
+    pub markdown_math: bool,
+    // Render `> [!NOTE]` blockquotes and `::: warning` containers as admonitions
+    #[serde(default)]
+    pub markdown_admonitions: bool,
+    // Render ```svgbob and ```dot code blocks to inline svg
+    #[serde(default)]
+    pub render_diagrams: bool,
     // Detect headers and generate small identifiers and a list
     // of all headers, so that they can be listed in a sidebar
     #[serde(default)]
@@ -66,24 +89,100 @@ pub struct ConfigRenderer {
     /// The filename will be `buildcache.techou`
     #[serde(default)]
     pub store_build_cache: bool,
-    // FIXME: Currently unused because it has to be static in syntect
+    /// Minify all generated HTML files, including inline CSS and JS
+    #[serde(default)]
+    pub minify_html: bool,
+    /// Check the internal links in the generated files after each build
+    #[serde(default)]
+    pub check_links: bool,
+    /// The syntect theme for code highlighting: The name of a bundled theme
+    /// (e.g. `InspiredGitHub`) or the path to a `.tmTheme` file in the project
+    #[serde(default)]
+    pub highlight_theme: String,
+    /// Highlight with css classes (and write the css for the theme) or inline styles
+    #[serde(default)]
+    pub highlight_style: HighlightStyle,
+    /// The prefix of the css classes of highlighted code
     #[serde(default)]
     pub highlight_prefix: String,
+    /// The css file for the highlighting theme, within the output folder
+    #[serde(default)]
+    pub highlight_css_file: String,
+    /// A folder in the project with additional `.sublime-syntax` grammars
+    /// which are used besides the bundled ones (and the embedded Swift grammar)
+    #[serde(default)]
+    pub syntaxes_folder: String,
+    /// The root folder of the project, to find the `highlight_theme` and the `syntaxes_folder`
+    #[serde(skip)]
+    pub project_root: PathBuf,
+    /// Commands that highlight code blocks of a language instead of syntect,
+    /// e.g. `swift = "SplashHTMLGen"`. They get the code on stdin and write html to stdout.
+    #[serde(default)]
+    pub external_highlighters: HashMap<String, String>,
+}
+
+/// How highlighted code is styled
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HighlightStyle {
+    /// `<span class="prefix-keyword">` with the css in `highlight_css_file`
+    Classes,
+    /// `<span style="color:#...">`
+    Inline,
+}
+
+impl Default for HighlightStyle {
+    fn default() -> Self {
+        HighlightStyle::Classes
+    }
+}
+
+impl ConfigRenderer {
+    /// The pulldown-cmark extensions that are enabled, for rendering and the search index
+    pub fn markdown_options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.markdown_tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.markdown_footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.markdown_strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.markdown_tasklists);
+        options.set(
+            Options::ENABLE_SMART_PUNCTUATION,
+            self.markdown_smart_punctuation,
+        );
+        options.set(
+            Options::ENABLE_HEADING_ATTRIBUTES,
+            self.markdown_heading_attributes,
+        );
+        options
+    }
 }
 
 impl Default for ConfigRenderer {
     fn default() -> Self {
         ConfigRenderer {
             highlight_syntax: true,
-            swift_use_splash: false,
             markdown_tables: true,
             markdown_footnotes: true,
+            markdown_strikethrough: true,
+            markdown_tasklists: true,
+            markdown_smart_punctuation: false,
+            markdown_heading_attributes: true,
+            markdown_math: false,
+            markdown_admonitions: true,
+            render_diagrams: true,
             parse_headers: true,
             parse_links: true,
-            section_header_identifier_template: "<span id=\"{identifier}-{number}\"></span>"
-                .to_owned(),
+            section_header_identifier_template: "<span id=\"{anchor}\"></span>".to_owned(),
             store_build_cache: true,
-            highlight_prefix: "techou".to_string(),
+            minify_html: false,
+            check_links: false,
+            highlight_theme: "InspiredGitHub".to_string(),
+            highlight_style: HighlightStyle::Classes,
+            highlight_prefix: String::new(),
+            highlight_css_file: "css/highlight.css".to_string(),
+            syntaxes_folder: "syntaxes".to_string(),
+            project_root: PathBuf::new(),
+            external_highlighters: HashMap::new(),
         }
     }
 }
@@ -108,6 +207,19 @@ pub struct ConfigFolders {
     pub public_folder: String,
     #[serde(default)]
     pub public_copy_folders: Vec<String>,
+    /// The templates for the shortcodes, within the public folder
+    #[serde(default)]
+    pub shortcodes_folder: String,
+    /// `.toml`, `.json`, `.yaml` and `.csv` files that are available in the
+    /// templates as `data.<filename>`
+    #[serde(default)]
+    pub data_folder: String,
+
+    /// Mirror the folder of a document within the posts, pages or collection
+    /// folder into the slug. `posts/2021/swift/foo.md` is written to
+    /// `{postsFolderName}/2021/swift/...` instead of `{postsFolderName}/...`
+    #[serde(default)]
+    pub slugs_from_folders: bool,
 
     /// Name of book folders including the summary toml file
     #[serde(default)]
@@ -143,6 +255,12 @@ impl ConfigFolders {
     pub fn output_folder_path(&self) -> PathBuf {
         self.root.join(&self.output_folder)
     }
+    pub fn shortcodes_folder_path(&self) -> PathBuf {
+        self.public_folder_path().join(&self.shortcodes_folder)
+    }
+    pub fn data_folder_path(&self) -> PathBuf {
+        self.root.join(&self.data_folder)
+    }
 
     pub fn output_posts_folder_path(&self) -> PathBuf {
         self.output_folder_path().join(&self.posts_folder_name)
@@ -185,6 +303,9 @@ impl Default for ConfigFolders {
             output_folder: "html".to_string(),
             public_folder: "public".to_string(),
             public_copy_folders: vec!["css".to_string(), "img".to_string(), "js".to_string()],
+            shortcodes_folder: "shortcodes".to_string(),
+            data_folder: "data".to_string(),
+            slugs_from_folders: false,
 
             books: Vec::new(),
 
@@ -214,6 +335,9 @@ pub struct ConfigTemplates {
     pub chapter_template: String,
     #[serde(default)]
     pub year_template: String,
+    /// Lists all tags, keywords or categories with their number of posts
+    #[serde(default)]
+    pub overview_template: String,
 }
 
 impl Default for ConfigTemplates {
@@ -225,6 +349,125 @@ impl Default for ConfigTemplates {
             book_template: "book.html".to_string(),
             chapter_template: "chapter.html".to_string(),
             year_template: "year.html".to_string(),
+            overview_template: "overview.html".to_string(),
+        }
+    }
+}
+
+/// A user-defined taxonomy (e.g. `authors` or `series`) in addition to
+/// tags, keywords and categories
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConfigTaxonomy {
+    /// The name of the taxonomy. Registers a `url_<name>` template function
+    /// and the archives are available as `context.by_taxonomy.<name>`
+    #[serde(default)]
+    pub name: String,
+    /// The front matter key with the values. Defaults to the name
+    #[serde(default)]
+    pub key: String,
+    /// The folder name in the generated structure. Defaults to the name
+    #[serde(default)]
+    pub folder_name: String,
+    /// The template for the archive pages. Defaults to the list template
+    #[serde(default)]
+    pub template: String,
+}
+
+impl ConfigTaxonomy {
+    pub fn key(&self) -> &str {
+        match self.key.is_empty() {
+            true => &self.name,
+            false => &self.key,
+        }
+    }
+
+    pub fn folder_name(&self) -> &str {
+        match self.folder_name.is_empty() {
+            true => &self.name,
+            false => &self.folder_name,
+        }
+    }
+
+    pub fn template<'a>(&'a self, templates: &'a ConfigTemplates) -> &'a str {
+        match self.template.is_empty() {
+            true => &templates.list_template,
+            false => &self.template,
+        }
+    }
+}
+
+/// The order of the documents in a collection
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CollectionOrder {
+    /// Newest first
+    Date,
+    /// Oldest first
+    DateAscending,
+    Title,
+    Filename,
+}
+
+impl Default for CollectionOrder {
+    fn default() -> Self {
+        CollectionOrder::Date
+    }
+}
+
+/// A collection of documents besides posts, pages and books
+/// (e.g. `talks` or `projects`)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConfigCollection {
+    /// The name of the collection. The documents are available as
+    /// `context.collections.<name>`
+    #[serde(default)]
+    pub name: String,
+    /// The source folder, relative to the root. Defaults to the name
+    #[serde(default)]
+    pub folder: String,
+    /// The folder name in the generated structure. Defaults to the name
+    #[serde(default)]
+    pub folder_name: String,
+    /// The template for the documents. Defaults to the page template
+    #[serde(default)]
+    pub template: String,
+    #[serde(default)]
+    pub order: CollectionOrder,
+    /// Link each document to the previous and next one
+    #[serde(default)]
+    pub siblings: bool,
+    /// Calculate similar documents
+    #[serde(default)]
+    pub similarity: bool,
+    /// Write feeds into `{feedsFolderName}/collections/{name}`
+    #[serde(default)]
+    pub feeds: bool,
+    /// Write paginated index pages into `{folderName}/index.html`
+    #[serde(default)]
+    pub indexes: bool,
+}
+
+impl ConfigCollection {
+    pub fn folder_path(&self, folders: &ConfigFolders) -> PathBuf {
+        match self.folder.is_empty() {
+            true => folders.root.join(&self.name),
+            false => folders.root.join(&self.folder),
+        }
+    }
+
+    pub fn folder_name(&self) -> &str {
+        match self.folder_name.is_empty() {
+            true => &self.name,
+            false => &self.folder_name,
+        }
+    }
+
+    pub fn template<'a>(&'a self, templates: &'a ConfigTemplates) -> &'a str {
+        match self.template.is_empty() {
+            true => &templates.page_template,
+            false => &self.template,
         }
     }
 }
@@ -260,6 +503,7 @@ impl Default for ConfigProject {
             render_one_page_books: false,
             debug_instrumentation: false,
             fast_render: false,
+            render_drafts: false,
         }
     }
 }
@@ -301,6 +545,149 @@ pub struct ConfigRSS {
     pub author_name: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigFeeds {
+    /// Write an Atom 1.0 feed. Default: `false`.
+    #[serde(default)]
+    pub atom: bool,
+    /// The file name of the Atom feed. Default: `atom.xml`.
+    #[serde(default)]
+    pub atom_address: String,
+    /// Write a JSON Feed 1.1. Default: `false`.
+    #[serde(default)]
+    pub json_feed: bool,
+    /// The file name of the JSON Feed. Default: `feed.json`.
+    #[serde(default)]
+    pub json_feed_address: String,
+    /// Include the full rendered content (with absolute URLs) instead of
+    /// just the description. Default: `false`.
+    #[serde(default)]
+    pub full_content: bool,
+    /// Write feeds for each tag. Default: `false`.
+    #[serde(default)]
+    pub per_tag: bool,
+    /// Write feeds for each category. Default: `false`.
+    #[serde(default)]
+    pub per_category: bool,
+    /// The folder for the per-tag and per-category feeds. Default: `feeds`.
+    #[serde(default)]
+    pub feeds_folder_name: String,
+    /// The title of the feeds. Falls back to the RSS or the project title.
+    #[serde(default)]
+    pub title: String,
+    /// Falls back to the RSS author
+    #[serde(default)]
+    pub author_name: String,
+    /// Falls back to the RSS author
+    #[serde(default)]
+    pub author_email: String,
+}
+
+impl Default for ConfigFeeds {
+    fn default() -> ConfigFeeds {
+        ConfigFeeds {
+            atom: false,
+            atom_address: "atom.xml".to_string(),
+            json_feed: false,
+            json_feed_address: "feed.json".to_string(),
+            full_content: false,
+            per_tag: false,
+            per_category: false,
+            feeds_folder_name: "feeds".to_string(),
+            title: String::new(),
+            author_name: String::new(),
+            author_email: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigAssets {
+    /// Compile Sass and write the CSS and JS files in `folders` with
+    /// fingerprinted names. Use `asset_url(path="css/style.scss")` in the templates
+    pub enable: bool,
+    /// The folders within the public folder with the assets
+    pub folders: Vec<String>,
+    pub minify: bool,
+    /// Add the hash of the contents to the filenames (`style.3f2a1b.css`)
+    pub fingerprint: bool,
+}
+
+impl Default for ConfigAssets {
+    fn default() -> ConfigAssets {
+        ConfigAssets {
+            enable: false,
+            folders: vec!["css".to_string(), "js".to_string()],
+            minify: true,
+            fingerprint: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigImages {
+    /// Write resized variants of the images in markdown and add `srcset`,
+    /// `width` and `height` attributes
+    pub enable: bool,
+    /// The widths of the resized variants. Only widths that are smaller than
+    /// the original are generated
+    pub widths: Vec<u32>,
+    /// Also write lossless WebP variants and use a `<picture>` element
+    pub webp: bool,
+    /// The `sizes` attribute of the images
+    pub sizes: String,
+    pub jpeg_quality: u8,
+    pub lazy_loading: bool,
+    /// The folder for the resized variants, within the output folder
+    pub output_folder_name: String,
+}
+
+impl Default for ConfigImages {
+    fn default() -> ConfigImages {
+        ConfigImages {
+            enable: false,
+            widths: vec![480, 960, 1600],
+            webp: false,
+            sizes: "100vw".to_string(),
+            jpeg_quality: 85,
+            lazy_loading: true,
+            output_folder_name: "img/generated".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConfigLanguages {
+    /// The language of all documents without a language
+    pub default_language: String,
+    /// Translations in these languages are detected via the filename
+    /// (`post.de.md`) or the `lang` front matter key and written to `/{lang}/...`
+    pub additional_languages: Vec<String>,
+}
+
+impl ConfigLanguages {
+    pub fn is_default(&self, lang: &str) -> bool {
+        lang == self.default_language
+    }
+
+    pub fn is_known(&self, lang: &str) -> bool {
+        self.is_default(lang) || self.additional_languages.iter().any(|l| l == lang)
+    }
+}
+
+impl Default for ConfigLanguages {
+    fn default() -> ConfigLanguages {
+        ConfigLanguages {
+            default_language: "en".to_string(),
+            additional_languages: Vec::new(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase", default)]
 pub struct ConfigSearch {
@@ -386,6 +773,22 @@ pub struct Config {
     #[serde(default, rename = "RSS")]
     pub rss: Option<ConfigRSS>,
 
+    /// Atom, JSON Feed and per-tag / per-category feeds
+    #[serde(rename = "Feeds", default)]
+    pub feeds: ConfigFeeds,
+
+    /// Sass, minification and fingerprinting
+    #[serde(rename = "Assets", default)]
+    pub assets: ConfigAssets,
+
+    /// Responsive images
+    #[serde(rename = "Images", default)]
+    pub images: ConfigImages,
+
+    /// Languages
+    #[serde(rename = "Languages", default)]
+    pub languages: ConfigLanguages,
+
     /// Search
     #[serde(rename = "Search", default)]
     pub search: ConfigSearch,
@@ -394,6 +797,14 @@ pub struct Config {
     #[serde(rename = "Render", default)]
     pub render: ConfigRenderer,
 
+    /// User-defined taxonomies
+    #[serde(default, rename = "Taxonomies", skip_serializing_if = "Vec::is_empty")]
+    pub taxonomies: Vec<ConfigTaxonomy>,
+
+    /// Collections of documents besides posts, pages and books
+    #[serde(default, rename = "Collections", skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<ConfigCollection>,
+
     /// Shortlinks
     #[serde(default, rename = "Shortlinks")]
     pub short_links: Option<HashMap<String, String>>,
@@ -442,6 +853,7 @@ impl Config {
 
         let mut configuration: Config = s.try_deserialize().unwrap();
         configuration.folders.root = in_folder.clone();
+        configuration.render.project_root = in_folder.clone();
         Ok(configuration)
     }
 
@@ -466,18 +878,118 @@ impl Config {
             "fastRender",
             "Fast rendering means we don't write tags, archives, search indexes etc.",
         );
+        docs.insert(
+            "renderDrafts",
+            "Render unpublished and scheduled posts as drafts (same as `--drafts`)",
+        );
         docs.insert(
             "debugInstrumentation",
             "Add additional debug information to the HTML",
         );
         docs.insert(
-            "swiftUseSplash",
-            "Proper Swift rendering doesn't work well with Syntect. Better rendering is provided by Splash (github.com/JohnSundell/Splash). Enabling this requires a Splash install on the system.",
+            "syntaxesFolder",
+            "Additional `.sublime-syntax` grammars for the code highlighting. Swift is built in",
+        );
+        docs.insert(
+            "[Render.externalHighlighters]",
+            "Highlight the code blocks of a language with a command (e.g. `swift = \"SplashHTMLGen\"`), which gets the code on stdin and writes html to stdout. If it fails, the code is rendered as plain text",
+        );
+        docs.insert(
+            "markdownSmartPunctuation",
+            "Typographic quotes, dashes and ellipses. Like the other markdown extensions, documents can override it with e.g. `markdown = { smartPunctuation = true }` in the front matter",
+        );
+        docs.insert(
+            "markdownHeadingAttributes",
+            "`# Heading {#custom-id .class}` for explicit heading anchors",
+        );
+        docs.insert(
+            "markdownMath",
+            "Render LaTeX math (`$inline$`, `$$display$$` and ```` ```math ```` blocks) to MathML at build time",
+        );
+        docs.insert(
+            "markdownAdmonitions",
+            "Render GitHub style `> [!NOTE]` / `> [!WARNING]` blockquotes and `::: tip Title` containers as `<aside class=\"admonition note\">`",
+        );
+        docs.insert(
+            "renderDiagrams",
+            "Render ```` ```svgbob ```` ASCII diagrams and ```` ```dot ```` graphs to inline svg at build time",
         );
         docs.insert("parseHeaders", "Detect headers and generate small identifiers and a list of all headers, so that they can be listed in a sidebar");
         docs.insert("parseLinks", "convert `lnk::link-id` with the shortlink and `rel::link` with the absolute link to the current root");
-        docs.insert("sectionHeaderIdentifierTemplate", "The HTML to use for the header sections that are parsed out of `h1` tags and can be used to populate a sidebar for longer articles or a toc");
+        docs.insert("sectionHeaderIdentifierTemplate", "The HTML to use for the header sections that are parsed out of `h1` tags and can be used to populate a sidebar for longer articles or a toc. `{anchor}` is the slugified heading (or the explicit `{#custom-id}`)");
         docs.insert("storeBuildCache", "If this is true, we save the buildcache to disk. This will enable faster rendering. The filename will be `buildcache.techou`");
+        docs.insert(
+            "fullContent",
+            "Include the full post in the feeds instead of the description",
+        );
+        docs.insert("atom", "Also write an Atom feed into `atomAddress`");
+        docs.insert("atomAddress", "The file name of the Atom feed");
+        docs.insert("jsonFeed", "Also write a JSON Feed into `jsonFeedAddress`");
+        docs.insert("jsonFeedAddress", "The file name of the JSON Feed");
+        docs.insert(
+            "perTag",
+            "Write a feed for each tag into the `feedsFolderName` folder",
+        );
+        docs.insert(
+            "perCategory",
+            "Write a feed for each category into the `feedsFolderName` folder",
+        );
+        docs.insert(
+            "feedsFolderName",
+            "The folder for the tag and category feeds",
+        );
+        docs.insert(
+            "title",
+            "The title of the feeds. `[Feeds]` falls back to the RSS or project title",
+        );
+        docs.insert(
+            "authorName",
+            "The author of the feeds. `[Feeds]` falls back to the RSS author",
+        );
+        docs.insert(
+            "authorEmail",
+            "The email of the feed author. `[Feeds]` falls back to the RSS author",
+        );
+        docs.insert(
+            "additionalLanguages",
+            "Translations (`post.de.md` or `lang = \"de\"`) are written into `/{lang}/` with their own indexes, feeds and search",
+        );
+        docs.insert(
+            "widths",
+            "The widths of the resized images in the `srcset` (if `[Images] enable` is set)",
+        );
+        docs.insert(
+            "webp",
+            "Also write lossless WebP variants of the resized images in a `<picture>` element",
+        );
+        docs.insert(
+            "fingerprint",
+            "Add a content hash to the compiled assets. Link them with `asset_url(path=\"css/style.scss\")`",
+        );
+        docs.insert(
+            "minifyHtml",
+            "Minify the generated HTML (code blocks are kept intact)",
+        );
+        docs.insert(
+            "checkLinks",
+            "Report broken internal links and anchors after each build. Use `techou check` to fail on them",
+        );
+        docs.insert(
+            "highlightTheme",
+            "The theme for code highlighting: `InspiredGitHub`, `base16-ocean.dark`, `base16-ocean.light`, `base16-eighties.dark`, `base16-mocha.dark`, `Solarized (dark)`, `Solarized (light)` or the path to a `.tmTheme` file",
+        );
+        docs.insert(
+            "highlightStyle",
+            "`classes` to highlight with css classes (the css is written to `highlightCssFile`) or `inline` for inline styles",
+        );
+        docs.insert(
+            "highlightPrefix",
+            "The prefix of the css classes for highlighted code. Empty keeps the markup of earlier versions (`<pre class=\"Rust\">` with `source rust`), `techou` gives `<pre class=\"techou-code Rust\">` with `techou-source techou-rust`",
+        );
+        docs.insert(
+            "highlightCssFile",
+            "Where the css for the highlighting theme is written to (within the output folder)",
+        );
         docs.insert("postsFolder", "Where are your posts");
         docs.insert(
             "pagesFolder",
@@ -488,6 +1000,18 @@ impl Config {
             "publicCopyFolders",
             "The file and folders that should be copied over from within the public folder",
         );
+        docs.insert(
+            "shortcodesFolder",
+            "The templates for `{{< name >}}` shortcodes in markdown, within the public folder",
+        );
+        docs.insert(
+            "dataFolder",
+            "Data files (toml, json, yaml, csv) that are available in all templates as `data.<filename>`",
+        );
+        docs.insert(
+            "slugsFromFolders",
+            "Mirror the sub folders of your posts and pages into their slugs",
+        );
         docs.insert(
             "dateFormat",
             "The input date format that should be used for your posts and apges",
@@ -500,13 +1024,10 @@ impl Config {
         let parsed = toml::to_string_pretty(&config).unwrap();
         let mut lines = Vec::new();
         for line in parsed.lines() {
-            for key in docs.keys() {
-                if line.starts_with(key) {
-                    if let Some(value) = docs.get(key) {
-                        lines.push(format!("# {}", value));
-                        break;
-                    }
-                }
+            // The whole key, so that `atom` doesn't document `atomAddress`
+            let key = line.split('=').next().unwrap_or_default().trim();
+            if let Some(value) = docs.get(key) {
+                lines.push(format!("# {}", value));
             }
             lines.push(format!("# {}", &line));
         }
@@ -521,6 +1042,29 @@ impl Config {
 # article2 = "https://example.com/article2.html"
 # bookarticle = "/articles/book.html"
 
+# Additional taxonomies besides tags, keywords and category.
+# Documents are grouped by the front matter `key`, written into `folderName`
+# and can be linked with `url_<name>(id=...)`
+# [[Taxonomies]]
+# name = "authors"
+# key = "authors"
+# folderName = "authors"
+# template = "list.html"
+
+# Collections of documents besides posts, pages and books.
+# The documents are available as `context.collections.<name>`.
+# `order` is one of "date", "dateAscending", "title" or "filename"
+# [[Collections]]
+# name = "talks"
+# folder = "talks"
+# folderName = "talks"
+# template = "page.html"
+# order = "date"
+# siblings = true
+# similarity = false
+# feeds = false
+# indexes = true
+
 # This is where you can add additional meta information.
 # they're available in all templates
 # [Meta]
@@ -572,5 +1116,53 @@ authorEmail = "example@example.com"
         let parsed =
             Config::from_toml(&default, std::path::PathBuf::from("/tmp/test.toml")).unwrap();
         assert_eq!(parsed.project.base_url, "https://example.com");
+        // Only the exact key is documented
+        assert!(default.contains("# Also write an Atom feed into `atomAddress`\n# atom = false"));
+        assert!(default.contains("# The file name of the Atom feed\n# atomAddress = "));
+    }
+
+    #[test]
+    fn test_parse_taxonomies() {
+        use crate::config::Config;
+        let contents = r#"
+[[Taxonomies]]
+name = "authors"
+
+[[Taxonomies]]
+name = "series"
+key = "serie"
+folderName = "all-series"
+"#;
+        let parsed =
+            Config::from_toml(&contents, std::path::PathBuf::from("/tmp/test.toml")).unwrap();
+        assert_eq!(parsed.taxonomies.len(), 2);
+        assert_eq!(parsed.taxonomies[0].folder_name(), "authors");
+        assert_eq!(parsed.taxonomies[1].key(), "serie");
+        assert_eq!(parsed.taxonomies[1].folder_name(), "all-series");
+    }
+
+    #[test]
+    fn test_parse_collections() {
+        use crate::config::{CollectionOrder, Config};
+        let contents = r#"
+[[Collections]]
+name = "talks"
+order = "dateAscending"
+siblings = true
+
+[[Collections]]
+name = "projects"
+folder = "content/projects"
+"#;
+        let parsed = Config::from_toml(&contents, std::path::PathBuf::from("/tmp")).unwrap();
+        assert_eq!(parsed.collections.len(), 2);
+        assert_eq!(parsed.collections[0].order, CollectionOrder::DateAscending);
+        assert!(parsed.collections[0].siblings);
+        assert!(!parsed.collections[0].indexes);
+        assert_eq!(parsed.collections[1].order, CollectionOrder::Date);
+        assert_eq!(
+            parsed.collections[1].folder_path(&parsed.folders),
+            std::path::PathBuf::from("/tmp/content/projects")
+        );
     }
 }
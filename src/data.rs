@@ -0,0 +1,100 @@
+use serde_json::{Map, Value};
+
+use crate::error::{Result, ResultContext, TechouError};
+use crate::io_utils::slurp;
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::read_dir;
+use std::path::Path;
+
+/// Load the `.toml`, `.json`, `.yaml` and `.csv` files in `folder`. They're
+/// available in the templates as `data.<filename>`. Sub folders become nested
+/// objects, so `data/menus/main.toml` is `data.menus.main`.
+/// If the folder doesn't exist, there's no data.
+pub fn load_data_files<A: AsRef<Path>>(folder: A) -> Result<BTreeMap<String, Value>> {
+    let folder = folder.as_ref();
+    let mut data = BTreeMap::new();
+    if !folder.is_dir() {
+        return Ok(data);
+    }
+    for entry in read_dir(folder).ctx(folder)? {
+        let path = entry.ctx(folder)?.path();
+        let name = match path.file_stem().and_then(OsStr::to_str) {
+            Some(name) if !name.starts_with('.') => name.to_string(),
+            _ => continue,
+        };
+        if path.is_dir() {
+            let nested = load_data_files(&path)?;
+            data.insert(name, Value::Object(nested.into_iter().collect()));
+            continue;
+        }
+        let value = match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => parse_toml(&slurp(&path)?, &path)?,
+            Some("json") => serde_json::from_str(&slurp(&path)?).ctx(&path)?,
+            Some("yaml") | Some("yml") => parse_yaml(&slurp(&path)?, &path)?,
+            Some("csv") => parse_csv(&slurp(&path)?, &path)?,
+            _ => {
+                println!("Ignoring unsupported data file: {:?}", &path);
+                continue;
+            }
+        };
+        data.insert(name, value);
+    }
+    Ok(data)
+}
+
+fn parse_toml(contents: &str, path: &Path) -> Result<Value> {
+    let value: toml::Value = toml::from_str(contents).ctx(path)?;
+    Ok(crate::front_matter::toml_to_json(value))
+}
+
+fn parse_yaml(contents: &str, path: &Path) -> Result<Value> {
+    serde_yaml::from_str(contents).map_err(|e| TechouError::Other {
+        issue: format!("{:?}: Invalid YAML: {}", path, &e),
+    })
+}
+
+/// Each row becomes an object with the values of the header row as keys
+fn parse_csv(contents: &str, path: &Path) -> Result<Value> {
+    let invalid = |e: csv::Error| TechouError::Other {
+        issue: format!("{:?}: Invalid CSV: {}", path, &e),
+    };
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader.headers().map_err(invalid)?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(invalid)?;
+        let row: Map<String, Value> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_csv() {
+        use super::parse_csv;
+        let contents = "name,company\nJane,Acme\nJohn,\"Initech, Inc\"\n";
+        let value = parse_csv(contents, std::path::Path::new("speakers.csv")).unwrap();
+        assert_eq!(value[0]["name"], "Jane");
+        assert_eq!(value[1]["company"], "Initech, Inc");
+    }
+
+    #[test]
+    fn test_toml() {
+        use super::parse_toml;
+        let contents = r#"
+[[items]]
+title = "Home"
+url = "/"
+"#;
+        let value = parse_toml(contents, std::path::Path::new("menu.toml")).unwrap();
+        assert_eq!(value["items"][0]["url"], "/");
+    }
+}
@@ -0,0 +1,108 @@
+use layout::backends::svg::SVGWriter;
+use layout::gv::{DotParser, GraphBuilder};
+
+use crate::build_cache::BuildCache;
+use crate::config::Config;
+use crate::error::{Result, TechouError};
+use crate::utils;
+
+/// The diagram languages that are rendered to svg
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagramKind {
+    /// ASCII diagrams via svgbob
+    Svgbob,
+    /// Graphviz `dot` graphs via layout-rs
+    Dot,
+}
+
+impl DiagramKind {
+    /// The kind for the language of a fenced code block
+    pub fn from_language(language: &str) -> Option<DiagramKind> {
+        match language {
+            "svgbob" | "bob" => Some(DiagramKind::Svgbob),
+            "dot" | "graphviz" => Some(DiagramKind::Dot),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagramKind::Svgbob => "svgbob",
+            DiagramKind::Dot => "dot",
+        }
+    }
+}
+
+/// Renders diagram code blocks to inline svg. The svgs are stored in the
+/// `BuildCache` by the hash of the diagram source.
+#[derive(Clone)]
+pub struct DiagramRenderer {
+    enabled: bool,
+    cache: BuildCache,
+}
+
+impl DiagramRenderer {
+    pub fn new(config: &Config, cache: &BuildCache) -> DiagramRenderer {
+        DiagramRenderer {
+            enabled: config.render.render_diagrams,
+            cache: cache.clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn render(&self, kind: DiagramKind, source: &str) -> Result<String> {
+        let key = format!("diagram:{}:{}", kind.name(), utils::hash_string(source, 16));
+        if let Some(svg) = self.cache.get_artifact::<String>(&key) {
+            return Ok(svg);
+        }
+        let svg = match kind {
+            DiagramKind::Svgbob => svgbob::to_svg(source),
+            DiagramKind::Dot => dot_to_svg(source)?,
+        };
+        self.cache.set_artifact(&key, &svg);
+        Ok(svg)
+    }
+}
+
+fn dot_to_svg(source: &str) -> Result<String> {
+    let mut parser = DotParser::new(source);
+    let graph = parser.process().map_err(|e| TechouError::Other {
+        issue: format!("Could not parse dot diagram: {}", e),
+    })?;
+    let mut builder = GraphBuilder::new();
+    builder.visit_graph(&graph);
+    let mut visual_graph = builder.get();
+    let mut writer = SVGWriter::new();
+    visual_graph.do_it(false, false, false, &mut writer);
+    Ok(writer.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_diagrams() {
+        let config = Config::default();
+        let cache = BuildCache::new("/tmp/no-cache.techou");
+        let renderer = DiagramRenderer::new(&config, &cache);
+        let svg = renderer
+            .render(DiagramKind::Svgbob, "+---+\n| a |--> b\n+---+")
+            .unwrap();
+        assert!(svg.contains("<svg"));
+        let svg = renderer
+            .render(DiagramKind::Dot, "digraph { a -> b; }")
+            .unwrap();
+        assert!(svg.contains("<svg"));
+        // Rendered from the cache
+        let key = format!(
+            "diagram:dot:{}",
+            utils::hash_string("digraph { a -> b; }", 16)
+        );
+        assert_eq!(cache.get_artifact::<String>(&key), Some(svg));
+        assert!(renderer.render(DiagramKind::Dot, "digraph {").is_err());
+    }
+}
@@ -2,13 +2,20 @@ use chrono::Datelike;
 use rayon::prelude::*;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::build_cache::BuildCache;
 use crate::config::Config;
+use crate::diagrams::DiagramRenderer;
 use crate::error::{Result, TechouError};
 use crate::front_matter::{parse_front_matter, FrontMatter};
+use crate::images::{DocumentImages, ImageProcessor};
+use crate::io_utils::GitDates;
 use crate::markdown::*;
+use crate::shortcodes::{ShortcodeDocument, Shortcodes};
 use crate::utils;
+use crate::wiki_links::preprocess_wiki_links;
 
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DocumentLink {
@@ -18,6 +25,66 @@ pub struct DocumentLink {
     pub slug: String,
 }
 
+/// A heading of a document with the headings below it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Section {
+    pub level: u32,
+    pub anchor: String,
+    pub title: String,
+    #[serde(default)]
+    pub children: Vec<Section>,
+}
+
+impl Section {
+    /// Nest sections by their level: Each section becomes a child of the closest
+    /// previous section with a lower level
+    pub fn nested(sections: Vec<Section>) -> Vec<Section> {
+        let mut roots: Vec<Section> = Vec::new();
+        let mut open: Vec<Section> = Vec::new();
+        for section in sections {
+            while open
+                .last()
+                .map(|last| last.level >= section.level)
+                .unwrap_or(false)
+            {
+                close_section(&mut open, &mut roots);
+            }
+            open.push(section);
+        }
+        while !open.is_empty() {
+            close_section(&mut open, &mut roots);
+        }
+        roots
+    }
+
+    /// All sections and their children, in document order
+    pub fn flattened(sections: &[Section]) -> Vec<&Section> {
+        let mut all = Vec::new();
+        for section in sections {
+            all.push(section);
+            all.append(&mut Section::flattened(&section.children));
+        }
+        all
+    }
+}
+
+fn close_section(open: &mut Vec<Section>, roots: &mut Vec<Section>) {
+    if let Some(section) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(section),
+            None => roots.push(section),
+        }
+    }
+}
+
+/// A translation of a document into another language
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Translation {
+    pub lang: String,
+    pub title: String,
+    pub slug: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Document {
     pub identifier: String,
@@ -26,11 +93,44 @@ pub struct Document {
     pub slug: String,
     pub content: String,
     pub raw_content: String,
-    pub sections: Vec<(String, String)>,
+    pub sections: Vec<Section>,
     pub similar_documents: Vec<(u32, DocumentLink)>,
     pub previous_document: Option<DocumentLink>,
     pub next_document: Option<DocumentLink>,
     pub updated: bool,
+    #[serde(default)]
+    pub lang: String,
+    #[serde(default)]
+    pub translation_key: String,
+    /// The same document in other languages. Will be injected
+    #[serde(default)]
+    pub translations: Vec<Translation>,
+    /// The documents that link to this one via wiki links. Will be injected
+    #[serde(default)]
+    pub backlinks: Vec<DocumentLink>,
+    /// The local images in the content, so that their resized variants can be
+    /// written for cached documents
+    #[serde(default)]
+    pub images: Vec<PathBuf>,
+}
+
+/// Everything besides the config that is needed to render the markdown of a document
+pub struct Renderers {
+    pub shortcodes: Shortcodes,
+    pub images: ImageProcessor,
+    pub diagrams: DiagramRenderer,
+    pub git_dates: GitDates,
+}
+
+impl Renderers {
+    pub fn new(config: &Config, cache: &BuildCache) -> Result<Renderers> {
+        Ok(Renderers {
+            shortcodes: Shortcodes::new(config.folders.shortcodes_folder_path())?,
+            images: ImageProcessor::new(config, cache),
+            diagrams: DiagramRenderer::new(config, cache),
+            git_dates: GitDates::new(&config.folders.root),
+        })
+    }
 }
 
 impl AsRef<Document> for Document {
@@ -46,6 +146,7 @@ impl Document {
         path: A,
         slug_base: &str,
         config: &Config,
+        renderers: &Renderers,
         book_html_root: Option<&str>,
     ) -> Result<Document> {
         let filename = path
@@ -57,22 +158,46 @@ impl Document {
             })?
             .to_string();
         let identifier = utils::hash_string(&filename, 8);
-        let (info, article) = parse_front_matter(&contents, &path.as_ref(), &config)?;
-        let slug = slug_from_frontmatter(&info, slug_base);
+        let (mut info, article) = parse_front_matter(&contents, &path.as_ref(), &config)?;
+        if info.updated_date.is_none() {
+            if let Some(updated) = renderers.git_dates.last_modified(&path) {
+                info.set_updated(updated, &config);
+            }
+        }
+        let (lang, translation_key) = language_from(&info, path.as_ref(), config);
+        let slug = match config.languages.is_default(&lang) {
+            true => slug_from_frontmatter(&info, slug_base),
+            false => format!("/{}{}", &lang, slug_from_frontmatter(&info, slug_base)),
+        };
+        let article = renderers.shortcodes.render(
+            article,
+            &ShortcodeDocument {
+                identifier: &identifier,
+                filename: &filename,
+                slug: &slug,
+                info: &info,
+            },
+            config,
+        )?;
+        let article = preprocess_wiki_links(&article);
         let formatted_root =
             book_html_root.map(|value| format!("{}/{}", &config.folders.books_folder_name, &value));
-        let ParseResult { content, sections } = markdown_to_html(
-            article,
-            &identifier,
+        let ParseResult {
+            content,
+            sections,
+            images,
+        } = markdown_to_html(
+            &article,
             &config.short_links,
             formatted_root.as_ref().map(String::as_str),
-            &config.render,
+            &info.markdown.apply(&config.render),
             info.limit_parsed_sections,
+            Some(DocumentImages {
+                processor: &renderers.images,
+                folder: path.as_ref().parent(),
+            }),
+            Some(&renderers.diagrams),
         );
-        let sections = sections
-            .into_iter()
-            .map(|(number, title)| (format!("{}-{}", &identifier, &number), title))
-            .collect();
         Ok(Document {
             identifier,
             filename,
@@ -85,6 +210,11 @@ impl Document {
             next_document: None,
             previous_document: None,
             updated: true,
+            lang,
+            translation_key,
+            translations: Vec::new(),
+            backlinks: Vec::new(),
+            images,
         })
     }
 
@@ -94,7 +224,8 @@ impl Document {
         slug: &str,
         filename: &str,
         info: &FrontMatter,
-        sections: Vec<(String, String)>,
+        sections: Vec<Section>,
+        lang: &str,
     ) -> Document {
         Document {
             identifier: utils::hash_string(&slug, 4),
@@ -108,11 +239,24 @@ impl Document {
             next_document: None,
             previous_document: None,
             updated: true,
+            lang: lang.to_string(),
+            translation_key: String::new(),
+            translations: Vec::new(),
+            backlinks: Vec::new(),
+            images: Vec::new(),
         }
     }
 }
 
 impl Document {
+    pub fn translation(&self) -> Translation {
+        Translation {
+            lang: self.lang.clone(),
+            title: self.info.title.clone(),
+            slug: self.slug.clone(),
+        }
+    }
+
     pub fn link(&self) -> DocumentLink {
         DocumentLink {
             identifier: self.identifier.clone(),
@@ -127,14 +271,18 @@ pub fn documents_in_folder<A: AsRef<Path>>(
     folder: A,
     base: &str,
     config: &Config,
-    cache: &crate::build_cache::BuildCache,
+    renderers: &Renderers,
+    cache: &BuildCache,
     force_update: bool,
 ) -> Result<Vec<Document>> {
-    use crate::io_utils::{contents_of_directory, slurp};
-    let files = contents_of_directory(folder.as_ref(), "md")?;
-    let posts: Vec<Document> = files
+    use crate::io_utils::{contents_of_directory, copy_bundle_resources, slurp};
+    let folder = folder.as_ref();
+    let files = contents_of_directory(folder, "md")?;
+    let posts: Vec<(&PathBuf, Document)> = files
         .par_iter()
         .filter_map(|path| {
+            let base = slug_base_for(folder, path, base, config);
+
             let contents = match slurp(path) {
                 Ok(c) => c,
                 Err(e) => {
@@ -147,24 +295,111 @@ pub fn documents_in_folder<A: AsRef<Path>>(
             let cache_key = &path.to_str().unwrap();
             if let Some(mut existing) = clone.get_item(cache_key, &contents) {
                 existing.updated = force_update;
-                return Some(existing);
+                renderers.images.write_variants(&existing.images);
+                if !should_render(&mut existing, &config) {
+                    return None;
+                }
+                return Some((path, existing));
             }
 
-            let post = match Document::new(&contents, &path, &base, &config, None) {
+            let mut post = match Document::new(&contents, &path, &base, &config, renderers, None) {
                 Ok(a) => a,
                 Err(e) => {
                     println!("Invalid Format {:?}: {:?}", &path, &e);
                     return None;
                 }
             };
-            if post.info.published == false {
+            if !should_render(&mut post, &config) {
                 return None;
             }
-            cache.set_item(&cache_key, &post);
-            Some(post)
+            // Drafts are not cached, so that they're written once they're published
+            if !post.info.draft {
+                cache.set_item(&cache_key, &post);
+            }
+            Some((path, post))
+        })
+        .collect();
+
+    // Copy the images, downloads, etc next to the documents into their output folders
+    let output_folder = config.folders.output_folder_path();
+    let bundles: BTreeSet<(&Path, PathBuf)> = posts
+        .iter()
+        .filter_map(|(path, post)| {
+            let target = output_folder.join(post.slug.trim_start_matches('/'));
+            Some((path.parent()?, target.parent()?.to_path_buf()))
         })
         .collect();
-    Ok(posts)
+    let mut copied = HashMap::new();
+    for (from_dir, to_dir) in bundles {
+        if let Err(e) = copy_bundle_resources(from_dir, "md", &to_dir, &mut copied) {
+            println!("Could not copy resources from {:?}: {:?}", &from_dir, &e);
+        }
+    }
+
+    Ok(posts.into_iter().map(|(_, post)| post).collect())
+}
+
+/// The language of a document and the key that links it to its translations.
+/// The language is taken from the front matter or the filename (`post.de.md`)
+fn language_from(info: &FrontMatter, path: &Path, config: &Config) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let (stem, suffix) = match stem.rsplit_once('.') {
+        Some((stem, suffix)) if config.languages.is_known(suffix) => (stem, Some(suffix)),
+        _ => (stem, None),
+    };
+    if let Some(lang) = &info.lang {
+        if !config.languages.is_known(lang) {
+            println!(
+                "{:?}: Unknown language `{}`, add it to `additionalLanguages`",
+                path, lang
+            );
+        }
+    }
+    let lang = info
+        .lang
+        .as_deref()
+        .or(suffix)
+        .unwrap_or(&config.languages.default_language)
+        .to_string();
+    let translation_key = match &info.translation_key {
+        Some(key) => key.clone(),
+        None => path.with_file_name(stem).to_string_lossy().to_string(),
+    };
+    (lang, translation_key)
+}
+
+/// The slug base of a document. If `slugs_from_folders` is enabled, the folders
+/// between `folder` and the document are appended to `base`
+fn slug_base_for(folder: &Path, path: &Path, base: &str, config: &Config) -> String {
+    if !config.folders.slugs_from_folders {
+        return base.to_string();
+    }
+    let relative: Vec<String> = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(folder).ok())
+        .map(|relative| {
+            relative
+                .components()
+                .filter_map(|c| c.as_os_str().to_str())
+                .map(|c| c.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    match relative.is_empty() {
+        true => base.to_string(),
+        false => format!("{}/{}", base, relative.join("/")),
+    }
+}
+
+/// Unpublished documents and documents with a `created` date in the future are drafts.
+/// Drafts are only rendered if `render_drafts` is enabled. Returns `false` if the
+/// document should be skipped.
+pub fn should_render(document: &mut Document, config: &Config) -> bool {
+    document.info.draft = !document.info.published || document.info.is_scheduled();
+    !document.info.draft || config.project.render_drafts
 }
 
 fn slug_from_frontmatter(front_matter: &FrontMatter, slug_base: &str) -> String {
@@ -205,4 +440,48 @@ this is the actual article contents yeah."#;
         let slug = document::slug_from_frontmatter(&frontmatter, "posts");
         assert_eq!(slug, "/posts/2009-12-30-hello-world.html");
     }
+
+    #[test]
+    fn test_slug_base_from_folders() {
+        use crate::config::Config;
+        use crate::document;
+        use std::path::Path;
+        let mut config = Config::default();
+        let folder = Path::new("/site/posts");
+        let path = Path::new("/site/posts/2021/swift/foo.md");
+        assert_eq!(
+            document::slug_base_for(folder, path, "posts", &config),
+            "posts"
+        );
+        config.folders.slugs_from_folders = true;
+        assert_eq!(
+            document::slug_base_for(folder, path, "posts", &config),
+            "posts/2021/swift"
+        );
+        let path = Path::new("/site/posts/foo.md");
+        assert_eq!(
+            document::slug_base_for(folder, path, "posts", &config),
+            "posts"
+        );
+    }
+
+    #[test]
+    fn test_language() {
+        use crate::config::Config;
+        use crate::document;
+        use crate::front_matter;
+        use std::path::Path;
+        let mut config = Config::default();
+        config.languages.additional_languages = vec!["de".to_string()];
+        let contents = "[frontMatter]\ntitle = \"Hallo\"\ncreated = \"2009-12-30\"\n---\n";
+        let (info, _) = front_matter::parse_front_matter(&contents, "a.md", &config).unwrap();
+        let (lang, key) = document::language_from(&info, Path::new("/posts/hello.de.md"), &config);
+        assert_eq!(lang, "de");
+        assert_eq!(key, "/posts/hello");
+        let (lang, key) = document::language_from(&info, Path::new("/posts/hello.md"), &config);
+        assert_eq!(lang, "en");
+        assert_eq!(key, "/posts/hello");
+        let (lang, _) = document::language_from(&info, Path::new("/posts/v1.2.md"), &config);
+        assert_eq!(lang, "en");
+    }
 }
@@ -1,7 +1,8 @@
-use crate::document::{Document, DocumentLink};
+use crate::config::{CollectionOrder, ConfigTaxonomy};
+use crate::document::{Document, DocumentLink, Translation};
 use crate::list::*;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub fn posts_by_date<'a>(posts: &'a [Document]) -> Vec<Year<'a>> {
     let mut date_map: BTreeMap<i32, BTreeMap<u32, Vec<&'a Document>>> = BTreeMap::new();
@@ -48,6 +49,60 @@ where
         .collect()
 }
 
+/// The categories of each user-defined taxonomy, by the name of the taxonomy
+pub fn posts_by_taxonomy<'a, D: AsRef<Document>>(
+    posts: &'a [D],
+    taxonomies: &[ConfigTaxonomy],
+) -> BTreeMap<String, Vec<Category<'a>>> {
+    taxonomies
+        .iter()
+        .map(|taxonomy| {
+            let mut categories = posts_by_array(posts, |p| {
+                p.info
+                    .taxonomies
+                    .get(&taxonomy.name)
+                    .map(|values| values.as_slice())
+                    .unwrap_or(&[])
+            });
+            categories.sort_by(|a, b| a.name.cmp(&b.name));
+            (taxonomy.name.clone(), categories)
+        })
+        .collect()
+}
+
+pub fn sort_documents(documents: &mut [Document], order: CollectionOrder) {
+    match order {
+        CollectionOrder::Date => {
+            documents.sort_by(|a, b| b.info.created_timestamp.cmp(&a.info.created_timestamp))
+        }
+        CollectionOrder::DateAscending => {
+            documents.sort_by(|a, b| a.info.created_timestamp.cmp(&b.info.created_timestamp))
+        }
+        CollectionOrder::Title => documents.sort_by(|a, b| a.info.title.cmp(&b.info.title)),
+        CollectionOrder::Filename => documents.sort_by(|a, b| a.filename.cmp(&b.filename)),
+    }
+}
+
+/// Link the documents with the same translation key to each other
+pub fn make_translations(for_documents: &mut [Document]) {
+    let mut translations: HashMap<String, Vec<Translation>> = HashMap::new();
+    for document in for_documents.iter() {
+        translations
+            .entry(document.translation_key.clone())
+            .or_insert_with(Vec::new)
+            .push(document.translation());
+    }
+    for document in for_documents.iter_mut() {
+        if let Some(all) = translations.get(&document.translation_key) {
+            document.translations = all
+                .iter()
+                .filter(|t| t.lang != document.lang)
+                .cloned()
+                .collect();
+        }
+    }
+}
+
 pub fn make_similarity(for_documents: &mut Vec<Document>, amount: usize) {
     // FIXME: Do I really need to do it this complicated? Can't have &mut and & (obviously)
     // so iterating over items while also calculating something for all items is impossible
@@ -190,6 +245,28 @@ this."#,
             previous_document: None,
             next_document: None,
             updated: true,
+            lang: "en".to_string(),
+            translation_key: iden.to_owned(),
+            translations: Vec::new(),
+            backlinks: Vec::new(),
+            images: Vec::new(),
         }
     }
+
+    #[test]
+    fn test_translations() {
+        use crate::document_operations::make_translations;
+        let mut t1 = make_doc("1", "Hello", "", &[]);
+        let mut t2 = make_doc("2", "Hallo", "", &[]);
+        let t3 = make_doc("3", "Other", "", &[]);
+        t2.lang = "de".to_string();
+        t1.translation_key = "hello".to_string();
+        t2.translation_key = "hello".to_string();
+        let mut docs = vec![t1, t2, t3];
+        make_translations(&mut docs);
+        assert_eq!(docs[0].translations.len(), 1);
+        assert_eq!(docs[0].translations[0].title, "Hallo");
+        assert_eq!(docs[1].translations[0].lang, "en");
+        assert!(docs[2].translations.is_empty());
+    }
 }
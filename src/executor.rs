@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs::create_dir_all;
 
 use rayon::prelude::*;
@@ -5,17 +6,19 @@ use rayon::prelude::*;
 use crate::book::Book;
 use crate::build_cache::BuildCache;
 use crate::builder;
-use crate::config::Config;
-use crate::document::{documents_in_folder, Document};
+use crate::config::{CollectionOrder, Config, ConfigCollection};
+use crate::document::{documents_in_folder, Document, Renderers};
 use crate::document_operations::*;
 use crate::error::{Result, ResultContext};
-use crate::feeds;
+use crate::feeds::{self, FeedFiles};
 use crate::io_utils::*;
+use crate::link_checker::{self, BrokenLink};
 use crate::list::*;
 use crate::search::Searcher;
 use crate::sitemap::SiteMap;
 use crate::template::Templates;
 use crate::utils::DebugTimer;
+use crate::wiki_links::resolve_wiki_links;
 
 pub fn execute(
     config: &Config,
@@ -29,12 +32,19 @@ pub fn execute(
     Ok(())
 }
 
+/// Build once and return the broken links in the output, e.g. for CI
+pub fn check(config: &Config, cache: &BuildCache) -> Result<Vec<BrokenLink>> {
+    let mut config = config.clone();
+    config.render.check_links = true;
+    catchable_execute(&config, cache, false, None)
+}
+
 fn catchable_execute(
     config: &Config,
     cache: &BuildCache,
     watch_or_serve: bool,
     triggered_by_change: Option<&std::path::Path>,
-) -> Result<()> {
+) -> Result<Vec<BrokenLink>> {
     let mut timer = DebugTimer::begin(0, &config);
 
     let output_folder = config.folders.output_folder_path();
@@ -66,6 +76,26 @@ fn catchable_execute(
     if let Some(Some(f)) = triggered_by_change.map(|e| e.as_os_str().to_str()) {
         mutated_templates = f.contains(&config.folders.public_folder) && f.contains("html");
 
+        // The rendered shortcodes are part of the cached documents
+        if std::path::Path::new(f).starts_with(config.folders.shortcodes_folder_path()) {
+            cache.clear();
+        }
+
+        // The fingerprinted asset urls change
+        if config.assets.enable
+            && f.contains(&config.folders.public_folder)
+            && [".scss", ".sass", ".css", ".js"]
+                .iter()
+                .any(|extension| f.ends_with(extension))
+        {
+            mutated_templates = true;
+        }
+
+        // The data files are available in all templates, so everything has to be rendered again
+        if std::path::Path::new(f).starts_with(config.folders.data_folder_path()) {
+            mutated_templates = true;
+        }
+
         // Also, if there was a change in one of the copy folders, force copy them again.
         // This is all a bit too simple
         for folder in &config.folders.public_copy_folders {
@@ -79,6 +109,8 @@ fn catchable_execute(
         }
     }
 
+    let renderers = Renderers::new(&config, &cache)?;
+
     // create a search engine
     let mut searcher = Searcher::new(&config);
 
@@ -87,11 +119,23 @@ fn catchable_execute(
         &config.folders.posts_folder_path(),
         &config.folders.posts_folder_name,
         &config,
+        &renderers,
         &cache,
         mutated_templates,
     )?;
     timer.sub_step("Posts");
 
+    // Translated documents are written into `/{lang}/` with their own indexes, feeds and search
+    make_translations(&mut posts);
+    let (mut posts, translated_posts) = split_translations(posts, &config);
+    let mut languages: BTreeMap<String, LanguageDocuments> = BTreeMap::new();
+    for (lang, mut language_posts) in translated_posts {
+        sort_documents(&mut language_posts, CollectionOrder::Date);
+        make_document_siblings(&mut language_posts);
+        languages.entry(lang).or_default().posts = language_posts;
+    }
+    timer.sub_step("Translations");
+
     posts.sort_by(|a1, a2| {
         a2.info
             .created_timestamp
@@ -121,14 +165,26 @@ fn catchable_execute(
     timer.sub_step("Similarity");
 
     let mut template_writer = Templates::new(&config.folders.public_folder_path()).unwrap();
-
-    let pages = documents_in_folder(
+    template_writer.register_image_functions(&renderers.images);
+    template_writer.register_asset_functions(crate::assets::process_assets(&config)?);
+    timer.sub_step("Assets");
+    template_writer.set_data(crate::data::load_data_files(
+        config.folders.data_folder_path(),
+    )?);
+
+    let mut pages = documents_in_folder(
         &config.folders.pages_folder_path(),
         &config.folders.pages_folder_name,
         &config,
+        &renderers,
         &cache,
         mutated_templates,
     )?;
+    make_translations(&mut pages);
+    let (mut pages, translated_pages) = split_translations(pages, &config);
+    for (lang, language_pages) in translated_pages {
+        languages.entry(lang).or_default().pages = language_pages;
+    }
 
     timer.sub_step("Load Pages");
 
@@ -140,23 +196,78 @@ fn catchable_execute(
 
     timer.sub_step("Search Pages");
 
-    let books: Vec<Book> = config
+    let mut collections: BTreeMap<String, Vec<Document>> = BTreeMap::new();
+    for collection in &config.collections {
+        let mut documents = documents_in_folder(
+            &collection.folder_path(&config.folders),
+            collection.folder_name(),
+            &config,
+            &renderers,
+            &cache,
+            mutated_templates,
+        )?;
+        make_translations(&mut documents);
+        let (mut documents, translated_documents) = split_translations(documents, &config);
+        arrange_collection(&mut documents, collection, &config);
+        if config.search.enable && !config.project.fast_render {
+            for document in &documents {
+                searcher.index_document(document)?;
+            }
+        }
+        collections.insert(collection.name.clone(), documents);
+        for (lang, mut language_documents) in translated_documents {
+            arrange_collection(&mut language_documents, collection, &config);
+            languages
+                .entry(lang)
+                .or_default()
+                .collections
+                .insert(collection.name.clone(), language_documents);
+        }
+    }
+
+    timer.sub_step("Collections");
+
+    let mut books: Vec<Book> = config
         .folders
         .books
         .par_iter()
-        .filter_map(
-            |filename| match Book::new(&filename, &config, &cache, mutated_templates) {
+        .filter_map(|filename| {
+            match Book::new(&filename, &config, &renderers, &cache, mutated_templates) {
                 Ok(book) => Some(book),
                 Err(e) => {
                     println!("Error generating book {}: {}", &filename, &e);
                     None
                 }
-            },
-        )
+            }
+        })
         .collect();
 
     timer.sub_step("Books");
 
+    // Wiki links (`[[Title]]`) can point to any document, so they're resolved once all are loaded
+    let mut documents: Vec<&mut Document> = posts
+        .iter_mut()
+        .chain(pages.iter_mut())
+        .chain(collections.values_mut().flatten())
+        .chain(languages.values_mut().flat_map(|l| l.documents_mut()))
+        .collect();
+    for book in &mut books {
+        documents.append(&mut book.documents_mut());
+    }
+    let mut wiki_links = resolve_wiki_links(&mut documents);
+    for book in &mut books {
+        if let Some(complete_book) = book.complete_book.as_mut() {
+            wiki_links.resolve(complete_book);
+        }
+    }
+    let wiki_link_issues = wiki_links.into_issues();
+    if !config.render.check_links {
+        for issue in &wiki_link_issues {
+            println!("{}", issue);
+        }
+    }
+    timer.sub_step("Wiki Links");
+
     let by_year = posts_by_date(&posts);
     timer.sub_step("posts_by_date");
     let by_keyword = posts_by_array(&posts, |p| &p.info.keywords);
@@ -184,6 +295,9 @@ fn catchable_execute(
     by_tag.sort_by(|a, b| a.name.cmp(&b.name));
     timer.sub_step("All Posts");
 
+    let by_taxonomy = posts_by_taxonomy(&all_posts, &config.taxonomies);
+    timer.sub_step("by_taxonomy");
+
     if config.search.enable && !config.project.fast_render {
         for book in &books {
             searcher.index_book(book)?;
@@ -193,6 +307,7 @@ fn catchable_execute(
     timer.sub_step("Search Books");
 
     let context = DocumentContext {
+        lang: &config.languages.default_language,
         posts: &posts,
         all_posts: &all_posts,
         pages: &pages,
@@ -201,6 +316,8 @@ fn catchable_execute(
         by_tag: &by_tag,
         by_keyword: &by_keyword,
         by_category: &by_category,
+        by_taxonomy: &by_taxonomy,
+        collections: &collections,
     };
 
     template_writer.register_url_functions(&context, &config);
@@ -213,18 +330,43 @@ fn catchable_execute(
     timer.sub_step("Write Pages");
     builder.books(&books, &config.folders.books_folder_name)?;
     timer.sub_step("Write Books");
+    for collection in &config.collections {
+        if let Some(documents) = collections.get(&collection.name) {
+            builder.collection(collection, documents)?;
+        }
+    }
+    timer.sub_step("Write Collections");
     if !config.project.fast_render {
         builder.category(&by_tag, &config.folders.tags_folder_name)?;
+        builder.overview(&by_tag, &config.folders.tags_folder_name, "Tags")?;
         timer.sub_step("Write Tags");
     }
     if !config.project.fast_render {
         builder.category(&by_keyword, &config.folders.keywords_folder_name)?;
+        builder.overview(
+            &by_keyword,
+            &config.folders.keywords_folder_name,
+            "Keywords",
+        )?;
         timer.sub_step("Write Keywords");
     }
     if !config.project.fast_render {
         builder.category(&by_category, &config.folders.category_folder_name)?;
+        builder.overview(
+            &by_category,
+            &config.folders.category_folder_name,
+            "Categories",
+        )?;
         timer.sub_step("Write Categories");
     }
+    if !config.project.fast_render {
+        for taxonomy in &config.taxonomies {
+            if let Some(categories) = by_taxonomy.get(&taxonomy.name) {
+                builder.taxonomy(taxonomy, categories)?;
+            }
+        }
+        timer.sub_step("Write Taxonomies");
+    }
     if !config.project.fast_render {
         builder.years(&by_year, &config.folders.years_folder_name)?;
         timer.sub_step("Write Years");
@@ -246,25 +388,119 @@ fn catchable_execute(
     )?;
     timer.sub_step("Write Indexes");
 
-    // Write the feed
+    // Write the feeds
     if !config.project.fast_render {
-        if let Some(rss) = &config.rss {
-            feeds::write_posts_rss(
-                &posts,
-                &output_folder.join("feed.rss"),
-                &rss,
-                &config.project.base_url,
-            )?;
-            timer.sub_step("Write Feed");
+        feeds::write_feeds(&posts, &FeedFiles::main(&config), None, &config)?;
+        if config.feeds.per_tag {
+            for tag in &by_tag {
+                let files = FeedFiles::topic(&config, &config.folders.tags_folder_name, tag.name);
+                feeds::write_feeds(&tag.posts, &files, Some(tag.name), &config)?;
+            }
+        }
+        if config.feeds.per_category {
+            for category in &by_category {
+                let files =
+                    FeedFiles::topic(&config, &config.folders.category_folder_name, category.name);
+                feeds::write_feeds(&category.posts, &files, Some(category.name), &config)?;
+            }
+        }
+        for collection in config.collections.iter().filter(|c| c.feeds) {
+            if let Some(documents) = collections.get(&collection.name) {
+                let files = FeedFiles::topic(&config, "collections", &collection.name);
+                feeds::write_feeds(documents, &files, Some(&collection.name), &config)?;
+            }
+        }
+        timer.sub_step("Write Feeds");
+    }
+
+    for (lang, language) in &languages {
+        let language_posts = &language.posts;
+        let language_all_posts: Vec<&Document> = language_posts.iter().collect();
+        let language_by_year = posts_by_date(language_posts);
+        let mut language_by_tag = posts_by_array(language_posts, |p| &p.info.tags);
+        language_by_tag.sort_by(|a, b| a.name.cmp(&b.name));
+        let language_by_keyword = posts_by_array(language_posts, |p| &p.info.keywords);
+        let language_by_category = posts_by_array(language_posts, |p| &p.info.category);
+        let language_by_taxonomy = posts_by_taxonomy(language_posts, &config.taxonomies);
+        let language_context = DocumentContext {
+            lang,
+            posts: language_posts,
+            all_posts: &language_all_posts,
+            pages: &language.pages,
+            books: &books,
+            by_date: &language_by_year,
+            by_tag: &language_by_tag,
+            by_keyword: &language_by_keyword,
+            by_category: &language_by_category,
+            by_taxonomy: &language_by_taxonomy,
+            collections: &language.collections,
+        };
+        template_writer.register_url_functions(&language_context, &config);
+        let language_builder =
+            builder::Builder::with_context(language_context, &template_writer, &config);
+        language_builder.posts(language_posts)?;
+        language_builder.pages(&language.pages)?;
+        for collection in &config.collections {
+            if let Some(documents) = language.collections.get(&collection.name) {
+                language_builder.collection(collection, documents)?;
+            }
+        }
+        language_builder.indexes_paged(
+            language_posts,
+            config.project.posts_per_index as usize,
+            title_fn,
+            lang,
+        )?;
+        if config.project.fast_render {
+            continue;
+        }
+        let folder = |name: &str| format!("{}/{}", lang, name);
+        let folders = &config.folders;
+        language_builder.category(&language_by_tag, folder(&folders.tags_folder_name))?;
+        language_builder.overview(&language_by_tag, folder(&folders.tags_folder_name), "Tags")?;
+        language_builder.category(&language_by_keyword, folder(&folders.keywords_folder_name))?;
+        language_builder.overview(
+            &language_by_keyword,
+            folder(&folders.keywords_folder_name),
+            "Keywords",
+        )?;
+        language_builder.category(&language_by_category, folder(&folders.category_folder_name))?;
+        language_builder.overview(
+            &language_by_category,
+            folder(&folders.category_folder_name),
+            "Categories",
+        )?;
+        for taxonomy in &config.taxonomies {
+            if let Some(categories) = language_by_taxonomy.get(&taxonomy.name) {
+                language_builder.taxonomy(taxonomy, categories)?;
+            }
+        }
+        language_builder.years(&language_by_year, folder(&folders.years_folder_name))?;
+        let files = FeedFiles::language(&config, lang);
+        feeds::write_feeds(language_posts, &files, Some(lang), &config)?;
+        if config.search.enable {
+            let mut language_searcher = Searcher::new(&config);
+            for document in language.documents() {
+                language_searcher.index_document(document)?;
+            }
+            let search_index_output_path = output_folder
+                .join(lang)
+                .join(&config.search.search_index_file);
+            spit(search_index_output_path, &language_searcher.finalize()?)?;
         }
     }
+    timer.sub_step("Write Translations");
 
     // Write the assets
     copy_items_to_directory(
         &config.folders.public_copy_folders,
         &config.folders.public_folder_path(),
         &output_folder,
+        // The compiled assets were already written
+        |path| crate::assets::is_processed(path, &config),
     )?;
+    // After the copy, so that it is not replaced by a file from the public folder
+    crate::parse_event_handlers::highlight::write_theme_css(&config)?;
     timer.sub_step("Write Assets");
 
     // Write the search index
@@ -286,6 +522,10 @@ fn catchable_execute(
             sitemap.add_document(&post);
         }
 
+        for document in languages.values().flat_map(|l| l.documents()) {
+            sitemap.add_document(document);
+        }
+
         // FIXME: Terrible, we need a better way to handle the recusrive book structure
         /*for book in books {
             book.map(|chapter| {
@@ -297,10 +537,40 @@ fn catchable_execute(
             sitemap.add_document(&page);
         }
 
+        for document in collections.values().flatten() {
+            sitemap.add_document(document);
+        }
+
         sitemap.finish();
         timer.sub_step("Write Sitemap");
     }
 
+    if config.render.minify_html {
+        println!(
+            "Minified HTML: saved {} bytes",
+            template_writer.minified_bytes()
+        );
+    }
+
+    let mut broken_links = Vec::new();
+    if config.render.check_links {
+        let mut sources = link_checker::Sources::default();
+        let documents = all_posts
+            .iter()
+            .copied()
+            .chain(&pages)
+            .chain(collections.values().flatten())
+            .chain(languages.values().flat_map(|l| l.documents()))
+            .chain(books.iter().filter_map(|b| b.complete_book.as_ref()));
+        for document in documents {
+            sources.add(document);
+        }
+        broken_links = wiki_link_issues;
+        broken_links.append(&mut link_checker::check_links(config, &sources)?);
+        link_checker::print_report(&broken_links);
+        timer.sub_step("Check Links");
+    }
+
     timer.end();
 
     println!("Done");
@@ -308,5 +578,63 @@ fn catchable_execute(
         cache.write().unwrap();
     }
 
-    Ok(())
+    Ok(broken_links)
+}
+
+/// The translated documents of one of the `additionalLanguages`
+#[derive(Default)]
+struct LanguageDocuments {
+    posts: Vec<Document>,
+    pages: Vec<Document>,
+    collections: BTreeMap<String, Vec<Document>>,
+}
+
+impl LanguageDocuments {
+    fn documents(&self) -> impl Iterator<Item = &Document> {
+        self.posts
+            .iter()
+            .chain(self.pages.iter())
+            .chain(self.collections.values().flatten())
+    }
+
+    fn documents_mut(&mut self) -> impl Iterator<Item = &mut Document> {
+        self.posts
+            .iter_mut()
+            .chain(self.pages.iter_mut())
+            .chain(self.collections.values_mut().flatten())
+    }
+}
+
+/// Split the translations from the documents in the default language, by their language
+fn split_translations(
+    documents: Vec<Document>,
+    config: &Config,
+) -> (Vec<Document>, BTreeMap<String, Vec<Document>>) {
+    let mut default_documents = Vec::new();
+    let mut translations: BTreeMap<String, Vec<Document>> = BTreeMap::new();
+    for document in documents {
+        match config.languages.is_default(&document.lang) {
+            true => default_documents.push(document),
+            false => translations
+                .entry(document.lang.clone())
+                .or_insert_with(Vec::new)
+                .push(document),
+        }
+    }
+    (default_documents, translations)
+}
+
+/// Sort the documents of a collection and link them as configured
+fn arrange_collection(
+    documents: &mut Vec<Document>,
+    collection: &ConfigCollection,
+    config: &Config,
+) {
+    sort_documents(documents, collection.order);
+    if collection.siblings {
+        make_document_siblings(documents);
+    }
+    if collection.similarity && documents.len() >= 5 && !config.project.fast_render {
+        make_similarity(documents, 2);
+    }
 }
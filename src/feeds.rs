@@ -1,45 +1,415 @@
+use lazy_static::*;
+use regex::Regex;
 use rss::{extension, ChannelBuilder, Item, ItemBuilder};
+use serde_derive::Serialize;
 
-use crate::config::ConfigRSS;
+use crate::config::Config;
 use crate::document::Document;
-use crate::error::Result;
+use crate::error::{Result, ResultContext};
 use crate::io_utils::spit;
+use crate::utils::{self, escape_xml};
 
 use std::path::Path;
 
-pub fn write_posts_rss<A: AsRef<Path>>(
-    posts: &[Document],
+/// The output files (relative to the output folder) of one feed in each format
+pub struct FeedFiles {
+    pub rss: String,
+    pub atom: String,
+    pub json: String,
+}
+
+impl FeedFiles {
+    /// The feeds with all posts
+    pub fn main(config: &Config) -> FeedFiles {
+        FeedFiles {
+            rss: "feed.rss".to_string(),
+            atom: config.feeds.atom_address.clone(),
+            json: config.feeds.json_feed_address.clone(),
+        }
+    }
+
+    /// The feeds with all posts in an additional language, in `/{lang}/`
+    pub fn language(config: &Config, lang: &str) -> FeedFiles {
+        FeedFiles {
+            rss: format!("{}/feed.rss", lang),
+            atom: format!("{}/{}", lang, &config.feeds.atom_address),
+            json: format!("{}/{}", lang, &config.feeds.json_feed_address),
+        }
+    }
+
+    /// The feeds for one tag, category, etc. `kind` is the name of the folder
+    /// (e.g. `tags`) and `name` the name of the tag
+    pub fn topic(config: &Config, kind: &str, name: &str) -> FeedFiles {
+        let base = format!(
+            "{}/{}/{}",
+            &config.feeds.feeds_folder_name,
+            kind,
+            utils::slugify(name)
+        );
+        FeedFiles {
+            rss: format!("{}.rss", &base),
+            atom: format!("{}.xml", &base),
+            json: format!("{}.json", &base),
+        }
+    }
+}
+
+/// The information about the feed itself, shared by all formats
+struct FeedChannel<'a> {
+    title: String,
+    description: String,
+    author_name: String,
+    author_email: String,
+    base_url: &'a str,
+    feed_url: String,
+    full_content: bool,
+}
+
+impl<'a> FeedChannel<'a> {
+    fn new(config: &'a Config, feed_url: String, topic: Option<&str>) -> FeedChannel<'a> {
+        fn first_non_empty(items: &[Option<&String>]) -> String {
+            items
+                .iter()
+                .flatten()
+                .find(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .unwrap_or_default()
+        }
+        let rss = config.rss.as_ref();
+        let title = first_non_empty(&[
+            Some(&config.feeds.title),
+            rss.map(|r| &r.title),
+            Some(&config.project.title),
+        ]);
+        FeedChannel {
+            title: match topic {
+                Some(topic) => format!("{} - {}", &title, topic),
+                None => title,
+            },
+            description: first_non_empty(&[
+                rss.and_then(|r| r.description.as_ref()),
+                Some(&config.project.description),
+            ]),
+            author_name: first_non_empty(&[
+                Some(&config.feeds.author_name),
+                rss.and_then(|r| r.author_name.as_ref()),
+            ]),
+            author_email: first_non_empty(&[
+                Some(&config.feeds.author_email),
+                rss.map(|r| &r.author_email),
+            ]),
+            base_url: &config.project.base_url,
+            feed_url,
+            full_content: config.feeds.full_content,
+        }
+    }
+
+    fn content(&self, post: &Document) -> String {
+        if self.full_content {
+            absolute_urls(&post.content, self.base_url)
+        } else {
+            post.info.description.clone()
+        }
+    }
+}
+
+/// Write the feeds in all enabled formats.
+/// `topic` is the name of the tag or category that the posts belong to.
+pub fn write_feeds<D: AsRef<Document>>(
+    posts: &[D],
+    files: &FeedFiles,
+    topic: Option<&str>,
+    config: &Config,
+) -> Result<()> {
+    let output_folder = config.folders.output_folder_path();
+    let base_url = &config.project.base_url;
+    if let Some(rss) = &config.rss {
+        let feed_url = match topic {
+            Some(_) => absolute_url(base_url, &files.rss),
+            None => absolute_url(base_url, &rss.feed_address),
+        };
+        let channel = FeedChannel::new(config, feed_url, topic);
+        write_posts_rss(posts, &output_folder.join(&files.rss), &channel)?;
+    }
+    if config.feeds.atom {
+        let channel = FeedChannel::new(config, absolute_url(base_url, &files.atom), topic);
+        write_posts_atom(posts, &output_folder.join(&files.atom), &channel)?;
+    }
+    if config.feeds.json_feed {
+        let channel = FeedChannel::new(config, absolute_url(base_url, &files.json), topic);
+        write_posts_json_feed(posts, &output_folder.join(&files.json), &channel)?;
+    }
+    Ok(())
+}
+
+fn write_posts_rss<A: AsRef<Path>, D: AsRef<Document>>(
+    posts: &[D],
     to_path: A,
-    rss: &ConfigRSS,
-    base_url: &str,
+    channel: &FeedChannel,
 ) -> Result<()> {
-    let author = match &rss.author_name {
-        Some(name) => format!("{} ({})", &rss.author_email, name),
-        None => rss.author_email.clone(),
+    let author = match channel.author_name.is_empty() {
+        false => format!("{} ({})", &channel.author_email, &channel.author_name),
+        true => channel.author_email.clone(),
     };
     let items: Vec<Item> = posts
         .iter()
+        .map(|post| post.as_ref())
+        .filter(|post| !post.info.draft)
         .map(|post| {
-            let link = format!("{}/{}", &base_url, &post.slug);
-            ItemBuilder::default()
+            let link = absolute_url(channel.base_url, &post.slug);
+            // The last revision is published as `dc:date`
+            let mut dublin_core = extension::dublincore::DublinCoreExtension::default();
+            if let Some(updated) = post.info.updated_rfc3339() {
+                dublin_core.set_dates(vec![updated]);
+            }
+            let mut builder = ItemBuilder::default();
+            builder
                 .itunes_ext(extension::itunes::ITunesItemExtension::default())
-                .dublin_core_ext(extension::dublincore::DublinCoreExtension::default())
+                .dublin_core_ext(dublin_core)
                 .title(post.info.title.clone())
                 .link(link)
                 .description(post.info.description.clone())
                 .author(author.clone())
-                .pub_date(post.info.rfc2822())
-                .build()
+                .pub_date(post.info.rfc2822());
+            if channel.full_content {
+                builder.content(channel.content(post));
+            }
+            builder.build()
         })
         .collect();
-    let link = format!("{}/{}", &base_url, &rss.feed_address);
-    let mut channel = ChannelBuilder::default()
-        .title(rss.title.clone())
-        .link(link)
+    let mut rss_channel = ChannelBuilder::default()
+        .title(channel.title.clone())
+        .link(channel.feed_url.clone())
         .items(items)
         .build();
-    if let Some(desc) = &rss.description {
-        channel.set_description(desc.clone())
+    if !channel.description.is_empty() {
+        rss_channel.set_description(channel.description.clone())
+    }
+    spit(to_path, &rss_channel.to_string())
+}
+
+fn write_posts_atom<A: AsRef<Path>, D: AsRef<Document>>(
+    posts: &[D],
+    to_path: A,
+    channel: &FeedChannel,
+) -> Result<()> {
+    let posts: Vec<&Document> = posts
+        .iter()
+        .map(|post| post.as_ref())
+        .filter(|post| !post.info.draft)
+        .collect();
+    let updated = posts
+        .iter()
+        .map(|post| post.info.updated_date.unwrap_or(post.info.date))
+        .max()
+        .map(|date| rfc3339(&date))
+        .unwrap_or_else(|| rfc3339(&chrono::Local::now().naive_local()));
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!(
+        "  <title>{}</title>\n",
+        escape_xml(&channel.title)
+    ));
+    if !channel.description.is_empty() {
+        xml.push_str(&format!(
+            "  <subtitle>{}</subtitle>\n",
+            escape_xml(&channel.description)
+        ));
+    }
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\"/>\n",
+        escape_xml(&channel.feed_url)
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}/\"/>\n",
+        escape_xml(channel.base_url.trim_end_matches('/'))
+    ));
+    xml.push_str(&format!("  <id>{}</id>\n", escape_xml(&channel.feed_url)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", &updated));
+    // Atom requires an author
+    let author_name = match channel.author_name.is_empty() {
+        true => &channel.title,
+        false => &channel.author_name,
+    };
+    xml.push_str(&format!(
+        "  <author>\n    <name>{}</name>\n",
+        escape_xml(author_name)
+    ));
+    if !channel.author_email.is_empty() {
+        xml.push_str(&format!(
+            "    <email>{}</email>\n",
+            escape_xml(&channel.author_email)
+        ));
+    }
+    xml.push_str("  </author>\n");
+
+    for post in posts {
+        let link = absolute_url(channel.base_url, &post.slug);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&post.info.title)
+        ));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", escape_xml(&link)));
+        xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&link)));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            rfc3339(&post.info.date)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            rfc3339(&post.info.updated_date.unwrap_or(post.info.date))
+        ));
+        for tag in &post.info.tags {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape_xml(tag)));
+        }
+        if !post.info.description.is_empty() {
+            xml.push_str(&format!(
+                "    <summary>{}</summary>\n",
+                escape_xml(&post.info.description)
+            ));
+        }
+        if channel.full_content {
+            xml.push_str(&format!(
+                "    <content type=\"html\">{}</content>\n",
+                escape_xml(&channel.content(post))
+            ));
+        }
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    spit(to_path, &xml)
+}
+
+fn write_posts_json_feed<A: AsRef<Path>, D: AsRef<Document>>(
+    posts: &[D],
+    to_path: A,
+    channel: &FeedChannel,
+) -> Result<()> {
+    // https://www.jsonfeed.org/version/1.1/
+    #[derive(Serialize)]
+    struct JsonFeedAuthor<'a> {
+        name: &'a str,
+    }
+
+    #[derive(Serialize)]
+    struct JsonFeedItem<'a> {
+        id: String,
+        url: String,
+        title: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content_html: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content_text: Option<&'a str>,
+        #[serde(skip_serializing_if = "str::is_empty")]
+        summary: &'a str,
+        date_published: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        date_modified: Option<String>,
+        tags: &'a [String],
+    }
+
+    #[derive(Serialize)]
+    struct JsonFeed<'a> {
+        version: &'static str,
+        title: &'a str,
+        home_page_url: String,
+        feed_url: &'a str,
+        #[serde(skip_serializing_if = "str::is_empty")]
+        description: &'a str,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        authors: Vec<JsonFeedAuthor<'a>>,
+        items: Vec<JsonFeedItem<'a>>,
+    }
+
+    let items = posts
+        .iter()
+        .map(|post| post.as_ref())
+        .filter(|post| !post.info.draft)
+        .map(|post| {
+            let url = absolute_url(channel.base_url, &post.slug);
+            JsonFeedItem {
+                id: url.clone(),
+                url,
+                title: &post.info.title,
+                content_html: match channel.full_content {
+                    true => Some(channel.content(post)),
+                    false => None,
+                },
+                // JSON Feed requires some content
+                content_text: match channel.full_content {
+                    true => None,
+                    false => Some(&post.info.description),
+                },
+                summary: &post.info.description,
+                date_published: rfc3339(&post.info.date),
+                date_modified: post.info.updated_date.as_ref().map(rfc3339),
+                tags: &post.info.tags,
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &channel.title,
+        home_page_url: format!("{}/", channel.base_url.trim_end_matches('/')),
+        feed_url: &channel.feed_url,
+        description: &channel.description,
+        authors: match channel.author_name.is_empty() {
+            true => Vec::new(),
+            false => vec![JsonFeedAuthor {
+                name: &channel.author_name,
+            }],
+        },
+        items,
+    };
+    let json = serde_json::to_string_pretty(&feed).ctx("Writing JSON Feed")?;
+    spit(to_path, &json)
+}
+
+/// Join the base url and an absolute or relative path
+fn absolute_url(base_url: &str, path: &str) -> String {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path.to_string();
+    }
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Feed readers don't know where the content came from, so all root-relative
+/// links and images have to point to the base url
+fn absolute_urls(html: &str, base_url: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r#"(href|src)="/([^/])"#).unwrap();
+    }
+    let replacement = format!("$1=\"{}/$2", base_url.trim_end_matches('/'));
+    RE.replace_all(html, replacement.as_str()).to_string()
+}
+
+fn rfc3339(date: &chrono::NaiveDateTime) -> String {
+    use chrono::{DateTime, Utc};
+    DateTime::<Utc>::from_utc(*date, Utc).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_absolute_urls() {
+        use crate::feeds::*;
+        let html = r#"<a href="/posts/a.html">a</a><img src="/img/b.png"/><a href="//cdn.com/x">x</a><a href="https://example.org">y</a>"#;
+        let result = absolute_urls(html, "https://example.com/");
+        assert!(result.contains(r#"href="https://example.com/posts/a.html""#));
+        assert!(result.contains(r#"src="https://example.com/img/b.png""#));
+        assert!(result.contains(r#"href="//cdn.com/x""#));
+        assert!(result.contains(r#"href="https://example.org""#));
+        assert_eq!(
+            absolute_url("https://example.com", "/posts/a.html"),
+            "https://example.com/posts/a.html"
+        );
     }
-    spit(to_path, &channel.to_string())
 }
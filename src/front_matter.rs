@@ -2,14 +2,28 @@ use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
 use serde_derive::{Deserialize, Serialize};
 use toml::de::from_str;
 
-use crate::config::Config;
+use crate::config::{Config, ConfigRenderer};
 use crate::error::{Result, TechouError};
 use crate::markdown::*;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 
 static DEFAULT_FRONT_MATTER_SEP: &str = "\n---\n";
+static YAML_FRONT_MATTER_DELIMITER: &str = "---";
+static TOML_FRONT_MATTER_DELIMITER: &str = "+++";
+
+/// The front matter formats that techou understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    /// A TOML `[frontMatter]` table followed by a `---` line (the techou default)
+    Techou,
+    /// A Jekyll style YAML block enclosed in `---` lines
+    Yaml,
+    /// A Hugo style TOML block with flat keys enclosed in `+++` lines
+    Toml,
+}
 
 fn default_nativetime() -> NaiveDateTime {
     NaiveDate::from_ymd(2016, 7, 8).and_hms(9, 10, 11)
@@ -19,6 +33,66 @@ fn default_true() -> bool {
     true
 }
 
+/// Which headings become `sections`: The first n (`limit_parsed_sections = 5`)
+/// or the headings up to a level, optionally only the first n of them
+/// (`limit_parsed_sections = { depth = 3, count = 10 }`)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum SectionLimit {
+    Count(usize),
+    Depth {
+        depth: u32,
+        #[serde(default)]
+        count: Option<usize>,
+    },
+}
+
+impl SectionLimit {
+    /// Should a heading with `level` become a section if there're already `sections`
+    pub fn includes(&self, level: u32, sections: usize) -> bool {
+        match *self {
+            SectionLimit::Count(count) => sections < count,
+            SectionLimit::Depth { depth, count } => {
+                level <= depth && count.map(|count| sections < count).unwrap_or(true)
+            }
+        }
+    }
+}
+
+/// Overrides of the markdown extensions of `[Render]` for one document
+/// (`markdown = { smartPunctuation = true, tables = false }`)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MarkdownOverrides {
+    pub tables: Option<bool>,
+    pub footnotes: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub tasklists: Option<bool>,
+    pub smart_punctuation: Option<bool>,
+    pub heading_attributes: Option<bool>,
+}
+
+impl MarkdownOverrides {
+    /// The renderer configuration with these overrides
+    pub fn apply<'a>(&self, config: &'a ConfigRenderer) -> Cow<'a, ConfigRenderer> {
+        if *self == MarkdownOverrides::default() {
+            return Cow::Borrowed(config);
+        }
+        let mut config = config.clone();
+        config.markdown_tables = self.tables.unwrap_or(config.markdown_tables);
+        config.markdown_footnotes = self.footnotes.unwrap_or(config.markdown_footnotes);
+        config.markdown_strikethrough = self.strikethrough.unwrap_or(config.markdown_strikethrough);
+        config.markdown_tasklists = self.tasklists.unwrap_or(config.markdown_tasklists);
+        config.markdown_smart_punctuation = self
+            .smart_punctuation
+            .unwrap_or(config.markdown_smart_punctuation);
+        config.markdown_heading_attributes = self
+            .heading_attributes
+            .unwrap_or(config.markdown_heading_attributes);
+        Cow::Owned(config)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct DateInfo {
     pub year: i32,
@@ -74,6 +148,16 @@ pub struct FrontMatter {
     pub date: NaiveDateTime,
     #[serde(default)]
     pub date_info: DateInfo, // FIXME: Move all date/time info into this struct.
+    // When was this document last revised? If this is empty, it will be injected
+    // from the last git commit (or the modification time) of the file
+    #[serde(default)]
+    pub updated: String,
+    #[serde(default)]
+    pub updated_timestamp: i64,
+    #[serde(default)]
+    pub updated_date: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub updated_info: Option<DateInfo>,
     // The unique identifier will be injected (based on the title)
     #[serde(default)]
     pub identifier: String,
@@ -82,15 +166,56 @@ pub struct FrontMatter {
     #[serde(default = "default_true")]
     pub indexed: bool,
     #[serde(default)]
-    pub limit_parsed_sections: Option<usize>,
+    pub limit_parsed_sections: Option<SectionLimit>,
+    // Enable or disable markdown extensions for this document
+    #[serde(default)]
+    pub markdown: MarkdownOverrides,
+
+    // The language of the document, if it isn't part of the filename (`post.de.md`)
+    #[serde(default)]
+    pub lang: Option<String>,
+    // Documents with the same key are translations of each other.
+    // Defaults to the filename without the language
+    #[serde(default)]
+    pub translation_key: Option<String>,
+
+    // Will be injected: the values for the user-defined `[[Taxonomies]]`
+    #[serde(default)]
+    pub taxonomies: HashMap<String, Vec<String>>,
+
+    // Will be injected: `true` for unpublished or scheduled documents that are
+    // only rendered because drafts are enabled (`--drafts`)
+    #[serde(default)]
+    pub draft: bool,
 }
 
 impl FrontMatter {
+    /// Is the `created` date of this document still in the future?
+    pub fn is_scheduled(&self) -> bool {
+        self.date > chrono::Local::now().naive_local()
+    }
+
     pub fn rfc2822(&self) -> String {
         use chrono::{DateTime, Utc};
         let dt = DateTime::<Utc>::from_utc(self.date, Utc);
         dt.to_rfc2822()
     }
+
+    pub fn updated_rfc3339(&self) -> Option<String> {
+        use chrono::{DateTime, Utc};
+        self.updated_date
+            .map(|date| DateTime::<Utc>::from_utc(date, Utc).to_rfc3339())
+    }
+
+    /// Set the `updated` date and the values derived from it
+    pub fn set_updated(&mut self, date: NaiveDateTime, config: &Config) {
+        self.updated = date
+            .format(&config.dates.output_date_time_format)
+            .to_string();
+        self.updated_timestamp = date.timestamp();
+        self.updated_date = Some(date);
+        self.updated_info = Some(DateInfo::from(date));
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -106,21 +231,69 @@ pub fn parse_front_matter<'a, A: AsRef<Path>>(
     filename: A,
     config: &Config,
 ) -> Result<(FrontMatter, &'a str)> {
-    let (front_matter_raw, article) = detect_front_matter(&input, &filename, &config)?;
-    let parsed_front_matter: ParsedFrontMatter = match from_str(front_matter_raw) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err(TechouError::FrontMatter {
-                issue: format!("{:?}: Invalid Front Matter: {}", &filename.as_ref(), &e),
-            });
+    let (format, front_matter_raw, article) = detect_front_matter(&input, &filename, &config)?;
+    let (mut front_matter, meta, taxonomies) = match format {
+        FrontMatterFormat::Techou => {
+            let parsed_front_matter: ParsedFrontMatter = match from_str(front_matter_raw) {
+                Ok(s) => s,
+                Err(e) => {
+                    return Err(TechouError::FrontMatter {
+                        issue: format!("{:?}: Invalid Front Matter: {}", &filename.as_ref(), &e),
+                    });
+                }
+            };
+            // The user-defined taxonomies are not part of the `FrontMatter` struct
+            let taxonomies = match config.taxonomies.is_empty() {
+                true => HashMap::new(),
+                false => from_str::<toml::Value>(front_matter_raw)
+                    .ok()
+                    .and_then(|value| value.get("frontMatter").cloned())
+                    .map(|value| taxonomies_from(&toml_to_json(value), &config))
+                    .unwrap_or_default(),
+            };
+            (
+                parsed_front_matter.front_matter,
+                parsed_front_matter.meta,
+                taxonomies,
+            )
+        }
+        FrontMatterFormat::Yaml => {
+            let value: serde_json::Value =
+                serde_yaml::from_str(front_matter_raw).map_err(|e| TechouError::FrontMatter {
+                    issue: format!(
+                        "{:?}: Invalid YAML Front Matter: {}",
+                        &filename.as_ref(),
+                        &e
+                    ),
+                })?;
+            let taxonomies = taxonomies_from(&value, &config);
+            let (front_matter, meta) = imported_front_matter(value, &filename)?;
+            (front_matter, meta, taxonomies)
+        }
+        FrontMatterFormat::Toml => {
+            let value: toml::Value =
+                from_str(front_matter_raw).map_err(|e| TechouError::FrontMatter {
+                    issue: format!(
+                        "{:?}: Invalid TOML Front Matter: {}",
+                        &filename.as_ref(),
+                        &e
+                    ),
+                })?;
+            if value.get("frontMatter").is_some() {
+                return Err(TechouError::FrontMatter {
+                    issue: format!(
+                        "{:?}: Ambiguous Front Matter: `+++` blocks use flat keys, but a `[frontMatter]` table was found. Use either `+++` with flat keys or a `[frontMatter]` table followed by `---`",
+                        &filename.as_ref()
+                    ),
+                });
+            }
+            let value = toml_to_json(value);
+            let taxonomies = taxonomies_from(&value, &config);
+            let (front_matter, meta) = imported_front_matter(value, &filename)?;
+            (front_matter, meta, taxonomies)
         }
     };
 
-    let ParsedFrontMatter {
-        mut front_matter,
-        meta,
-    } = parsed_front_matter;
-
     if front_matter.created.is_empty() {
         front_matter.created = default_date_time(&config);
     }
@@ -128,27 +301,185 @@ pub fn parse_front_matter<'a, A: AsRef<Path>>(
     let (date_string, timestamp, date) = detect_date_time(&front_matter.created, &config)?;
 
     front_matter.meta = meta;
+    front_matter.taxonomies = taxonomies;
     front_matter.created_timestamp = timestamp;
     front_matter.created = date_string;
     front_matter.date = date;
     front_matter.date_info = DateInfo::from(date);
 
-    let ParseResult {
-        content,
-        sections: _,
-    } = markdown_to_html(
+    if !front_matter.updated.is_empty() {
+        let (_, _, updated) = detect_date_time(&front_matter.updated, &config)?;
+        front_matter.set_updated(updated, &config);
+    }
+
+    let ParseResult { content, .. } = markdown_to_html(
         &front_matter.description,
-        "",
         &None,
         None,
-        &config.render,
+        &front_matter.markdown.apply(&config.render),
         front_matter.limit_parsed_sections,
+        None,
+        None,
     );
     front_matter.description_html = content;
 
     Ok((front_matter, article))
 }
 
+/// Map the flat keys of a Jekyll / Hugo front matter onto our `FrontMatter`.
+/// Known aliases (`date`, `categories`, `excerpt`, `draft`, ...) are translated,
+/// all other scalar values end up in `meta`.
+fn imported_front_matter<A: AsRef<Path>>(
+    value: serde_json::Value,
+    filename: A,
+) -> Result<(FrontMatter, HashMap<String, String>)> {
+    use serde_json::{json, Value};
+    let mut map = match value {
+        Value::Object(map) => map,
+        Value::Null => serde_json::Map::new(),
+        _ => {
+            return Err(TechouError::FrontMatter {
+                issue: format!(
+                    "{:?}: Invalid Front Matter: Expected a list of keys and values",
+                    &filename.as_ref()
+                ),
+            });
+        }
+    };
+
+    fn take_string(map: &mut serde_json::Map<String, Value>, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| match map.remove(*key) {
+            Some(Value::String(s)) => Some(s),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        })
+    }
+
+    fn take_list(map: &mut serde_json::Map<String, Value>, keys: &[&str]) -> Vec<String> {
+        keys.iter()
+            .find_map(|key| map.remove(*key).as_ref().and_then(string_list))
+            .unwrap_or_default()
+    }
+
+    fn take_bool(map: &mut serde_json::Map<String, Value>, key: &str) -> Option<bool> {
+        match map.remove(key) {
+            Some(Value::Bool(b)) => Some(b),
+            _ => None,
+        }
+    }
+
+    let title = take_string(&mut map, &["title"]).ok_or_else(|| TechouError::FrontMatter {
+        issue: format!(
+            "{:?}: Invalid Front Matter: Missing `title`",
+            &filename.as_ref()
+        ),
+    })?;
+    // Imported posts are published unless they say otherwise
+    let draft = take_bool(&mut map, "draft").unwrap_or(false);
+    let published = take_bool(&mut map, "published").unwrap_or(!draft);
+
+    let mut front_matter = json!({
+        "title": title,
+        "tags": take_list(&mut map, &["tags"]),
+        "keywords": take_list(&mut map, &["keywords"]),
+        "category": take_list(&mut map, &["categories", "category"]),
+        "created": take_string(&mut map, &["date", "created"]).unwrap_or_default(),
+        "updated": take_string(&mut map, &["updated", "lastmod", "last_modified_at"]).unwrap_or_default(),
+        "description": take_string(&mut map, &["description", "excerpt", "summary"]).unwrap_or_default(),
+        "published": published,
+    });
+    if let Some(slug) = take_string(&mut map, &["slug"]) {
+        front_matter["slug"] = json!(slug);
+    }
+    if let Some(lang) = take_string(&mut map, &["lang", "language"]) {
+        front_matter["lang"] = json!(lang);
+    }
+    if let Some(key) = take_string(&mut map, &["translationKey", "translation_key"]) {
+        front_matter["translation_key"] = json!(key);
+    }
+    if let Some(indexed) = take_bool(&mut map, "indexed") {
+        front_matter["indexed"] = json!(indexed);
+    }
+    if let Some(limit) = map.remove("limit_parsed_sections") {
+        front_matter["limit_parsed_sections"] = limit;
+    }
+    if let Some(markdown) = map.remove("markdown") {
+        front_matter["markdown"] = markdown;
+    }
+
+    let mut meta = HashMap::new();
+    if let Some(Value::Object(explicit_meta)) = map.remove("meta") {
+        map.extend(explicit_meta);
+    }
+    for (key, value) in map {
+        match value {
+            Value::String(s) => meta.insert(key, s),
+            Value::Number(n) => meta.insert(key, n.to_string()),
+            Value::Bool(b) => meta.insert(key, b.to_string()),
+            _ => None,
+        };
+    }
+
+    let front_matter: FrontMatter =
+        serde_json::from_value(front_matter).map_err(|e| TechouError::FrontMatter {
+            issue: format!("{:?}: Invalid Front Matter: {}", &filename.as_ref(), &e),
+        })?;
+    Ok((front_matter, meta))
+}
+
+/// A list of strings, or a space-separated string (Jekyll allows this for tags)
+fn string_list(value: &serde_json::Value) -> Option<Vec<String>> {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => Some(s.split_whitespace().map(|s| s.to_string()).collect()),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(|item| match item {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Collect the values for the user-defined `[[Taxonomies]]` from the raw front matter
+fn taxonomies_from(
+    front_matter: &serde_json::Value,
+    config: &Config,
+) -> HashMap<String, Vec<String>> {
+    config
+        .taxonomies
+        .iter()
+        .filter_map(|taxonomy| {
+            let values = front_matter.get(taxonomy.key()).and_then(string_list)?;
+            Some((taxonomy.name.clone(), values))
+        })
+        .collect()
+}
+
+/// Convert TOML into JSON. TOML datetimes become strings so that they
+/// can be parsed with `detect_date_time`
+pub(crate) fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => Value::Object(
+            t.into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
 pub fn default_front_matter(title: &str, date: &str) -> String {
     format!(
         r#"[frontMatter]
@@ -166,11 +497,55 @@ pub fn join_front_matter_with_content(front_matter: &str, content: &str) -> Stri
     format!("{}{}{}", &front_matter, DEFAULT_FRONT_MATTER_SEP, &content)
 }
 
-fn detect_front_matter<'a, A: AsRef<Path>>(
+/// Split the input into the front matter and the article. The format of the front
+/// matter is detected from the delimiters:
+/// - `---` on the first line: YAML until the next `---` line
+/// - `+++` on the first line: TOML with flat keys until the next `+++` line
+/// - otherwise a TOML `[frontMatter]` table until the first `---` line
+pub(crate) fn detect_front_matter<'a, A: AsRef<Path>>(
     input: &'a str,
     filename: A,
     _config: &Config,
-) -> Result<(&'a str, &'a str)> {
+) -> Result<(FrontMatterFormat, &'a str, &'a str)> {
+    let trimmed = input.trim_start();
+    for (delimiter, format) in &[
+        (YAML_FRONT_MATTER_DELIMITER, FrontMatterFormat::Yaml),
+        (TOML_FRONT_MATTER_DELIMITER, FrontMatterFormat::Toml),
+    ] {
+        let mut lines = trimmed.split_inclusive('\n');
+        let start = match lines.next() {
+            Some(line) if line.trim_end() == *delimiter => line.len(),
+            _ => continue,
+        };
+        // The closing delimiter has to be on its own line
+        let mut offset = start;
+        let mut end = None;
+        for line in lines {
+            if line.trim_end() == *delimiter {
+                end = Some((offset, offset + line.len()));
+                break;
+            }
+            offset += line.len();
+        }
+        let (front_matter_end, article_start) = end.ok_or_else(|| TechouError::FrontMatter {
+            issue: format!(
+                "{:?}: Missing closing `{}` for the Front Matter",
+                &filename.as_ref(),
+                delimiter
+            ),
+        })?;
+        let front_matter = &trimmed[start..front_matter_end];
+        if *format == FrontMatterFormat::Yaml && front_matter.contains("[frontMatter]") {
+            return Err(TechouError::FrontMatter {
+                issue: format!(
+                    "{:?}: Ambiguous Front Matter: a `[frontMatter]` TOML table was found inside `---` YAML delimiters. Remove the leading `---` for techou front matter",
+                    &filename.as_ref()
+                ),
+            });
+        }
+        return Ok((*format, front_matter, &trimmed[article_start..]));
+    }
+
     let index = match input.find(DEFAULT_FRONT_MATTER_SEP) {
         Some(r) => r,
         None => {
@@ -180,7 +555,11 @@ fn detect_front_matter<'a, A: AsRef<Path>>(
         }
     };
     let (f, a) = input.split_at(index);
-    Ok((f, &a[DEFAULT_FRONT_MATTER_SEP.len()..]))
+    Ok((
+        FrontMatterFormat::Techou,
+        f,
+        &a[DEFAULT_FRONT_MATTER_SEP.len()..],
+    ))
 }
 
 pub fn default_date_time(config: &Config) -> String {
@@ -196,6 +575,13 @@ pub fn detect_date_time(input: &str, config: &Config) -> Result<(String, i64, Na
             NaiveDate::parse_from_str(&input, &config.dates.date_format)
                 .and_then(|e| Ok(e.and_hms(10, 30, 30)))
         })
+        // Imported front matter uses RFC 3339 (Hugo) or `2019-01-20 10:00:00 +0100` (Jekyll)
+        .or_else(|e| {
+            chrono::DateTime::parse_from_rfc3339(&input)
+                .or_else(|_| chrono::DateTime::parse_from_str(&input, "%Y-%m-%d %H:%M:%S %z"))
+                .map(|d| d.naive_local())
+                .map_err(|_| e)
+        })
         .map_err(|e| TechouError::FrontMatter {
             issue: format!("{:?}: Invalid Date Format in Front Matter: {}", &input, &e),
         })?;
@@ -222,7 +608,8 @@ this is the actual article contents yeah."#;
         let result =
             front_matter::detect_front_matter(contents, "testfile.md", &Default::default());
         assert!(result.is_ok());
-        let (front_matter, article) = result.unwrap();
+        let (format, front_matter, article) = result.unwrap();
+        assert_eq!(format, front_matter::FrontMatterFormat::Techou);
         assert!(front_matter.len() > 0);
         assert!(article.len() > 0);
         assert_eq!(article, "this is the actual article contents yeah.");
@@ -301,4 +688,161 @@ this."#;
         assert_eq!(fm.keywords.len(), 2);
         assert_eq!(fm.keywords[1], "second keyword");
     }
+
+    #[test]
+    fn test_yaml() {
+        use crate::front_matter;
+        let contents = r#"---
+title: "Hello Jekyll"
+date: 2019-01-20 10:00:00 +0100
+tags: [swift, rust]
+categories: programming
+layout: post
+---
+this is the actual article contents yeah."#;
+        let (fm, article) =
+            front_matter::parse_front_matter(&contents, "yeah.md", &Default::default()).unwrap();
+        assert_eq!(fm.title, "Hello Jekyll");
+        assert_eq!(fm.tags, vec!["swift", "rust"]);
+        assert_eq!(fm.category, vec!["programming"]);
+        assert_eq!(fm.date_info.year, 2019);
+        assert!(fm.published);
+        assert_eq!(fm.meta.get("layout"), Some(&"post".to_string()));
+        assert_eq!(article, "this is the actual article contents yeah.");
+    }
+
+    #[test]
+    fn test_flat_toml() {
+        use crate::front_matter;
+        let contents = r#"+++
+title = "Hello Hugo"
+date = 2019-01-20T10:00:00Z
+categories = ["programming"]
+draft = true
++++
+this."#;
+        let (fm, article) =
+            front_matter::parse_front_matter(&contents, "yeah.md", &Default::default()).unwrap();
+        assert_eq!(fm.title, "Hello Hugo");
+        assert_eq!(fm.date_info.month, 1);
+        assert!(!fm.published);
+        assert_eq!(article, "this.");
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        use crate::front_matter;
+        let contents = r#"---
+[frontMatter]
+title = "Hello World"
+---
+this."#;
+        let result = front_matter::parse_front_matter(&contents, "yeah.md", &Default::default());
+        assert!(result.is_err());
+        let contents = r#"+++
+title = "Hello World"
+"#;
+        let result = front_matter::parse_front_matter(&contents, "yeah.md", &Default::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_updated() {
+        use crate::front_matter;
+        let contents = r#"
+[frontMatter]
+title = "Hello World"
+created = "2009-12-30"
+updated = "2010-01-02 11:12:13"
+published = true
+---
+this."#;
+        let (fm, _) =
+            front_matter::parse_front_matter(&contents, "yeah.md", &Default::default()).unwrap();
+        assert_eq!(fm.updated_info.as_ref().map(|i| i.year), Some(2010));
+        assert!(fm.updated_timestamp > fm.created_timestamp);
+        assert!(fm.updated_rfc3339().is_some());
+    }
+
+    #[test]
+    fn test_taxonomies() {
+        use crate::config::{Config, ConfigTaxonomy};
+        use crate::front_matter;
+        let mut config = Config::default();
+        config.taxonomies.push(ConfigTaxonomy {
+            name: "authors".to_string(),
+            ..Default::default()
+        });
+        let contents = r#"
+[frontMatter]
+title = "Hello World"
+created = "2009-12-30"
+authors = ["Jane", "John"]
+---
+this."#;
+        let (fm, _) = front_matter::parse_front_matter(&contents, "yeah.md", &config).unwrap();
+        assert_eq!(
+            fm.taxonomies.get("authors"),
+            Some(&vec!["Jane".to_string(), "John".to_string()])
+        );
+        let contents = r#"---
+title: Hello Jekyll
+authors: Jane
+---
+this."#;
+        let (fm, _) = front_matter::parse_front_matter(&contents, "yeah.md", &config).unwrap();
+        assert_eq!(
+            fm.taxonomies.get("authors"),
+            Some(&vec!["Jane".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_section_limit() {
+        use crate::front_matter::{self, SectionLimit};
+        let contents = r#"
+[frontMatter]
+title = "Hello World"
+created = "2009-12-30"
+limit_parsed_sections = { depth = 3 }
+---
+Contents"#;
+        let (fm, _) =
+            front_matter::parse_front_matter(&contents, "yeah.md", &Default::default()).unwrap();
+        let limit = fm.limit_parsed_sections.unwrap();
+        assert_eq!(
+            limit,
+            SectionLimit::Depth {
+                depth: 3,
+                count: None
+            }
+        );
+        assert!(limit.includes(3, 100));
+        assert!(!limit.includes(4, 0));
+        assert!(!SectionLimit::Count(2).includes(1, 2));
+    }
+
+    #[test]
+    fn test_markdown_overrides() {
+        use crate::config::ConfigRenderer;
+        use crate::front_matter;
+        use pulldown_cmark::Options;
+        let contents = r#"
+[frontMatter]
+title = "Hello World"
+created = "2009-12-30"
+markdown = { smartPunctuation = true, tables = false }
+---
+Contents"#;
+        let (fm, _) =
+            front_matter::parse_front_matter(&contents, "yeah.md", &Default::default()).unwrap();
+        let config = ConfigRenderer::default();
+        let options = fm.markdown.apply(&config).markdown_options();
+        assert!(options.contains(Options::ENABLE_SMART_PUNCTUATION));
+        assert!(!options.contains(Options::ENABLE_TABLES));
+        assert!(options.contains(Options::ENABLE_FOOTNOTES));
+        assert!(!config
+            .markdown_options()
+            .contains(Options::ENABLE_SMART_PUNCTUATION));
+    }
 }
@@ -0,0 +1,306 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ColorType, DynamicImage, GenericImageView, ImageFormat};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::build_cache::BuildCache;
+use crate::config::{Config, ConfigImages};
+use crate::error::{Result, ResultContext, TechouError};
+use crate::utils::{self, escape_xml};
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// The image formats that are resized. Everything else (e.g. `svg` or
+/// animated `gif`) is used as it is
+const SUPPORTED_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+/// One resized variant of an image
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImageVariant {
+    pub width: u32,
+    pub height: u32,
+    pub url: String,
+    pub webp_url: Option<String>,
+}
+
+/// An image with all its resized variants. Stored in the `BuildCache` by the
+/// hash of the image contents.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<ImageVariant>,
+}
+
+/// The images processor and the folder of the document that is rendered, so
+/// that relative images (e.g. in page bundles) can be found
+#[derive(Clone, Copy)]
+pub struct DocumentImages<'a> {
+    pub processor: &'a ImageProcessor,
+    pub folder: Option<&'a Path>,
+}
+
+/// Writes resized variants of images and the `srcset` html for them
+#[derive(Clone)]
+pub struct ImageProcessor {
+    config: ConfigImages,
+    public_folder: PathBuf,
+    output_folder: PathBuf,
+    cache: BuildCache,
+}
+
+impl ImageProcessor {
+    pub fn new(config: &Config, cache: &BuildCache) -> ImageProcessor {
+        ImageProcessor {
+            config: config.images.clone(),
+            public_folder: config.folders.public_folder_path(),
+            output_folder: config.folders.output_folder_path(),
+            cache: cache.clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    /// The file for the `src` of an image. Absolute paths are resolved within the
+    /// public folder, relative paths within `folder`. Returns `None` for remote
+    /// images and unsupported formats.
+    pub fn source_path(&self, src: &str, folder: Option<&Path>) -> Option<PathBuf> {
+        if src.contains("://") || src.starts_with("//") || src.starts_with("data:") {
+            return None;
+        }
+        let extension = Path::new(src).extension()?.to_str()?.to_lowercase();
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+            return None;
+        }
+        let path = match src.strip_prefix('/') {
+            Some(absolute) => self.public_folder.join(absolute),
+            None => folder?.join(src),
+        };
+        match path.is_file() {
+            true => Some(path),
+            false => None,
+        }
+    }
+
+    /// Write the variants of `source` in all configured widths that are smaller than the image
+    pub fn process(&self, source: &Path) -> Result<ProcessedImage> {
+        let bytes = std::fs::read(source).ctx(source)?;
+        let hash = utils::hash_bytes(&bytes, 16);
+        let key = format!(
+            "image:{}:{:?}:{}:{}",
+            &hash, &self.config.widths, self.config.webp, self.config.jpeg_quality
+        );
+        if let Some(processed) = self.cache.get_artifact::<ProcessedImage>(&key) {
+            if processed.variants.iter().all(|v| self.exists(v)) {
+                return Ok(processed);
+            }
+        }
+        let image = image::load_from_memory(&bytes).map_err(|e| image_error(source, e))?;
+        let (width, height) = image.dimensions();
+        let mut widths: Vec<u32> = self
+            .config
+            .widths
+            .iter()
+            .copied()
+            .filter(|w| *w < width)
+            .collect();
+        widths.push(width);
+        widths.sort_unstable();
+        widths.dedup();
+        let variants = widths
+            .into_iter()
+            .map(|w| self.write_variant(&image, source, &hash, w))
+            .collect::<Result<Vec<_>>>()?;
+        let processed = ProcessedImage {
+            width,
+            height,
+            variants,
+        };
+        self.cache.set_artifact(&key, &processed);
+        Ok(processed)
+    }
+
+    /// Write the variants of images that a cached document uses again, in case
+    /// the output folder was removed since they were written
+    pub fn write_variants(&self, sources: &[PathBuf]) {
+        for source in sources {
+            if let Err(e) = self.process(source) {
+                println!("{}", &e);
+            }
+        }
+    }
+
+    /// A single variant of `source` with `width` (used by the `resize_image` template function)
+    pub fn resize(&self, source: &Path, width: u32) -> Result<ImageVariant> {
+        let bytes = std::fs::read(source).ctx(source)?;
+        let hash = utils::hash_bytes(&bytes, 16);
+        let key = format!(
+            "image:{}:{}:{}:{}",
+            &hash, width, self.config.webp, self.config.jpeg_quality
+        );
+        if let Some(variant) = self.cache.get_artifact::<ImageVariant>(&key) {
+            if self.exists(&variant) {
+                return Ok(variant);
+            }
+        }
+        let image = image::load_from_memory(&bytes).map_err(|e| image_error(source, e))?;
+        let variant = self.write_variant(&image, source, &hash, width.min(image.width()))?;
+        self.cache.set_artifact(&key, &variant);
+        Ok(variant)
+    }
+
+    /// The html for an image: An `img` with `srcset` or, if WebP is enabled,
+    /// a `picture` with a WebP `source`
+    pub fn html(&self, image: &ProcessedImage, src: &str, alt: &str, title: &str) -> String {
+        let srcset = |webp: bool| {
+            image
+                .variants
+                .iter()
+                .filter_map(|v| {
+                    let url = match webp {
+                        true => v.webp_url.as_ref()?,
+                        false => &v.url,
+                    };
+                    Some(format!("{} {}w", url, v.width))
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let sizes = escape_xml(&self.config.sizes);
+        let mut img = format!(
+            r#"<img src="{}" srcset="{}" sizes="{}" width="{}" height="{}" alt="{}""#,
+            escape_xml(src),
+            srcset(false),
+            &sizes,
+            image.width,
+            image.height,
+            escape_xml(alt)
+        );
+        if !title.is_empty() {
+            img.push_str(&format!(r#" title="{}""#, escape_xml(title)));
+        }
+        if self.config.lazy_loading {
+            img.push_str(r#" loading="lazy""#);
+        }
+        img.push_str(" />");
+        if !self.config.webp {
+            return img;
+        }
+        format!(
+            r#"<picture><source type="image/webp" srcset="{}" sizes="{}" />{}</picture>"#,
+            srcset(true),
+            &sizes,
+            img
+        )
+    }
+
+    fn write_variant(
+        &self,
+        image: &DynamicImage,
+        source: &Path,
+        hash: &str,
+        width: u32,
+    ) -> Result<ImageVariant> {
+        let format = ImageFormat::from_path(source).map_err(|e| image_error(source, e))?;
+        let resized = match width == image.width() {
+            true => image.clone(),
+            false => image.resize(width, u32::MAX, FilterType::Lanczos3),
+        };
+        let stem = source
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(utils::slugify)
+            .unwrap_or_default();
+        let extension = source
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let name = format!("{}-{}-{}", &stem, &hash[..8], width);
+        let url = self.url(&format!("{}.{}", &name, &extension));
+        let path = self.path(&url);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).ctx(parent)?;
+        }
+        match format {
+            ImageFormat::Jpeg => {
+                let rgb = resized.to_rgb8();
+                let file = BufWriter::new(File::create(&path).ctx(&path)?);
+                JpegEncoder::new_with_quality(file, self.config.jpeg_quality)
+                    .encode(&rgb, rgb.width(), rgb.height(), ColorType::Rgb8)
+                    .map_err(|e| image_error(&path, e))?;
+            }
+            _ => resized
+                .save_with_format(&path, format)
+                .map_err(|e| image_error(&path, e))?,
+        }
+        let webp_url = match self.config.webp {
+            true => {
+                let webp_url = self.url(&format!("{}.webp", &name));
+                let webp_path = self.path(&webp_url);
+                let rgba = resized.to_rgba8();
+                let file = BufWriter::new(File::create(&webp_path).ctx(&webp_path)?);
+                WebPEncoder::new_lossless(file)
+                    .encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+                    .map_err(|e| image_error(&webp_path, e))?;
+                Some(webp_url)
+            }
+            false => None,
+        };
+        Ok(ImageVariant {
+            width: resized.width(),
+            height: resized.height(),
+            url,
+            webp_url,
+        })
+    }
+
+    fn url(&self, filename: &str) -> String {
+        format!(
+            "/{}/{}",
+            self.config.output_folder_name.trim_matches('/'),
+            filename
+        )
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.output_folder.join(url.trim_start_matches('/'))
+    }
+
+    fn exists(&self, variant: &ImageVariant) -> bool {
+        self.path(&variant.url).exists()
+            && variant
+                .webp_url
+                .as_ref()
+                .map(|url| self.path(url).exists())
+                .unwrap_or(true)
+    }
+}
+
+fn image_error(path: &Path, error: image::ImageError) -> TechouError {
+    TechouError::Other {
+        issue: format!("{:?}: Could not process image: {}", path, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_source_path() {
+        use crate::build_cache::BuildCache;
+        use crate::config::Config;
+        use crate::images::ImageProcessor;
+        let config = Config::default();
+        let processor = ImageProcessor::new(&config, &BuildCache::new("/tmp/no-cache.techou"));
+        assert!(processor
+            .source_path("https://example.com/a.png", None)
+            .is_none());
+        assert!(processor.source_path("/img/a.svg", None).is_none());
+        assert!(processor.source_path("a.png", None).is_none());
+    }
+}
@@ -1,10 +1,9 @@
-use copy_dir::*;
-
 use crate::error::*;
 
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs::{create_dir_all, read_dir};
-use std::io::{prelude::*, ErrorKind};
+use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
 pub fn slurp<T: AsRef<Path>>(path: T) -> Result<String> {
@@ -55,6 +54,8 @@ pub fn generate_book_folders(
     Ok(())
 }
 
+/// All files of `file_type` in `directory` and its sub directories.
+/// Hidden files and folders are ignored.
 pub fn contents_of_directory<A: AsRef<Path>>(
     directory: A,
     file_type: &str,
@@ -65,18 +66,153 @@ pub fn contents_of_directory<A: AsRef<Path>>(
     for entry in read_dir(directory_path).ctx(directory_path)? {
         let entry = entry.ctx(directory_path)?;
         let path = entry.path();
+        if is_hidden(&path) {
+            continue;
+        }
         if path.is_dir() {
+            matches.append(&mut contents_of_directory(&path, file_type)?);
+            continue;
+        }
+        if path.extension() == Some(valid_type) {
+            matches.push(path);
+        }
+    }
+    Ok(matches)
+}
+
+/// Copy the files next to a document that are not of `file_type` (e.g. the images of
+/// a page bundle) from `from_dir` into `to_dir`. Files that are up to date are skipped.
+/// `copied` holds the source of every target, so that two folders that are copied
+/// into the same `to_dir` don't overwrite each other's files. Such collisions are
+/// skipped and returned as an error.
+pub fn copy_bundle_resources<A: AsRef<Path>, B: AsRef<Path>>(
+    from_dir: A,
+    file_type: &str,
+    to_dir: B,
+    copied: &mut HashMap<PathBuf, PathBuf>,
+) -> Result<()> {
+    let valid_type = OsStr::new(file_type);
+    let (from_dir, to_dir) = (from_dir.as_ref(), to_dir.as_ref());
+    let mut collisions = Vec::new();
+    for entry in read_dir(from_dir).ctx(from_dir)? {
+        let entry = entry.ctx(from_dir)?;
+        let path = entry.path();
+        if is_hidden(&path) || path.is_dir() || path.extension() == Some(valid_type) {
             continue;
         }
-        match path.extension() {
-            Some(x) if x == valid_type => matches.push(path),
-            _ => {
-                println!("Invalid file: {:?}", &path);
+        let target = match path.file_name() {
+            Some(name) => to_dir.join(name),
+            None => continue,
+        };
+        match copied.get(&target) {
+            Some(source) if source != &path => {
+                collisions.push(format!("{:?} (already copied from {:?})", &path, source));
                 continue;
             }
+            Some(_) => continue,
+            None => {
+                copied.insert(target.clone(), path.clone());
+            }
+        }
+        if is_up_to_date(&target, &path) {
+            continue;
+        }
+        create_dir_all(to_dir).ctx(to_dir)?;
+        std::fs::copy(&path, &target).ctx(&path)?;
+    }
+    match collisions.is_empty() {
+        true => Ok(()),
+        false => Err(TechouError::Other {
+            issue: format!(
+                "Resources with the same name in {:?}: {}",
+                to_dir,
+                collisions.join(", ")
+            ),
+        }),
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// The times of the last git commits of the files in the project. The history is
+/// read with a single `git log` per build instead of one call per document
+#[derive(Default)]
+pub struct GitDates {
+    dates: HashMap<PathBuf, i64>,
+}
+
+impl GitDates {
+    /// Read the history of the git repository that contains `folder`. Empty if
+    /// `folder` isn't in a repository or git isn't installed
+    pub fn new<A: AsRef<Path>>(folder: A) -> GitDates {
+        use std::process::Command;
+        let folder = match folder.as_ref() {
+            p if p.as_os_str().is_empty() => Path::new("."),
+            p => p,
+        };
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(&["-c", "core.quotePath=false"])
+                .args(args)
+                .current_dir(folder)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+        };
+        let root = match git(&["rev-parse", "--show-toplevel"]) {
+            Some(root) => PathBuf::from(root.trim()),
+            None => return GitDates::default(),
         };
+        let log = git(&["log", "--name-only", "--format=%x00%ct", "--", "."]).unwrap_or_default();
+        GitDates {
+            dates: parse_git_log(&root, &log),
+        }
+    }
+
+    /// The time of the last git commit that touched `path`, or the modification
+    /// time of the file if it isn't tracked by git
+    pub fn last_modified<A: AsRef<Path>>(&self, path: A) -> Option<chrono::NaiveDateTime> {
+        use chrono::{Local, TimeZone};
+        let path = path.as_ref();
+        let git_timestamp = path
+            .canonicalize()
+            .ok()
+            .and_then(|path| self.dates.get(&path).copied());
+        let timestamp = match git_timestamp {
+            Some(timestamp) => timestamp,
+            None => std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?
+                .as_secs() as i64,
+        };
+        Some(Local.timestamp(timestamp, 0).naive_local())
     }
-    Ok(matches)
+}
+
+/// The newest commit time of each file in a `git log --name-only --format=%x00%ct`.
+/// Commits start with a NUL, followed by the files they touched
+fn parse_git_log(root: &Path, log: &str) -> HashMap<PathBuf, i64> {
+    let mut dates = HashMap::new();
+    let mut timestamp = None;
+    for line in log.lines() {
+        if let Some(commit) = line.strip_prefix('\0') {
+            timestamp = commit.trim().parse::<i64>().ok();
+            continue;
+        }
+        if let (Some(timestamp), false) = (timestamp, line.is_empty()) {
+            // The log starts with the newest commit
+            dates.entry(root.join(line)).or_insert(timestamp);
+        }
+    }
+    dates
 }
 
 pub fn clear_directory<A: AsRef<Path>>(directory: A) -> Result<()> {
@@ -93,10 +229,15 @@ pub fn clear_directory<A: AsRef<Path>>(directory: A) -> Result<()> {
     Ok(())
 }
 
-pub fn copy_items_to_directory<A: AsRef<Path>>(
+/// Copy the `items` (files or folders) from `from_dir` into `to_dir`. Folders are
+/// copied file by file, so that they're merged with folders that already exist in
+/// `to_dir` (e.g. with compiled assets or resized images). Files for which `skip`
+/// returns `true` and files that are up to date are left out.
+pub fn copy_items_to_directory<A: AsRef<Path>, F: Fn(&Path) -> bool>(
     items: &[String],
     from_dir: A,
     to_dir: A,
+    skip: F,
 ) -> Result<()> {
     for entry in items {
         let source = from_dir.as_ref().join(entry);
@@ -106,21 +247,66 @@ pub fn copy_items_to_directory<A: AsRef<Path>>(
         };
         let target = to_dir.as_ref().join(entry);
         println!("copy '{:?}' to '{:?}'", &source, &target);
-        // We copy each item seperately, so we can see when it fails
-        match copy_dir(&source, &target) {
-            Ok(ref e) if !e.is_empty() => {
-                e.iter().for_each(|_| println!("Could not copy {:?}", &e))
-            }
-            Err(e) => match &e {
-                err @ std::io::Error { .. } => match err.kind() {
-                    ErrorKind::AlreadyExists => (),
-                    _ => println!("Copy Error: {:?}", &e),
-                },
-            },
-            _ => (),
-        };
+        copy_item(&source, &target, &skip)?;
+    }
+    Ok(())
+}
 
-        //println!("Copy Error: {:?}", &e),
+fn copy_item(source: &Path, target: &Path, skip: &dyn Fn(&Path) -> bool) -> Result<()> {
+    if skip(source) {
+        return Ok(());
+    }
+    if source.is_dir() {
+        create_dir_all(target).ctx(target)?;
+        for entry in read_dir(source).ctx(source)? {
+            let path = entry.ctx(source)?.path();
+            if let Some(name) = path.file_name() {
+                copy_item(&path, &target.join(name), skip)?;
+            }
+        }
+        return Ok(());
     }
+    if is_up_to_date(target, source) {
+        return Ok(());
+    }
+    if let Some(parent) = target.parent() {
+        create_dir_all(parent).ctx(parent)?;
+    }
+    std::fs::copy(source, target).ctx(source)?;
     Ok(())
 }
+
+/// Is `target` at least as new as the `source` it was copied from?
+fn is_up_to_date(target: &Path, source: &Path) -> bool {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    target.exists() && modified(target) >= modified(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_resource_collisions() {
+        let folder = std::env::temp_dir().join("techou-bundle-test");
+        let _ = std::fs::remove_dir_all(&folder);
+        for name in ["a", "b"] {
+            create_dir_all(folder.join(name)).unwrap();
+            std::fs::write(folder.join(name).join("image.png"), name).unwrap();
+        }
+        let output = folder.join("output");
+        let mut copied = HashMap::new();
+        copy_bundle_resources(folder.join("a"), "md", &output, &mut copied).unwrap();
+        assert!(copy_bundle_resources(folder.join("b"), "md", &output, &mut copied).is_err());
+        assert_eq!(slurp(output.join("image.png")).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_parse_git_log() {
+        let log = "\02000\n\nposts/a.md\n\n\01000\n\nposts/a.md\nposts/b.md\n";
+        let dates = parse_git_log(Path::new("/site"), log);
+        assert_eq!(dates[Path::new("/site/posts/a.md")], 2000);
+        assert_eq!(dates[Path::new("/site/posts/b.md")], 1000);
+        assert_eq!(dates.len(), 2);
+    }
+}
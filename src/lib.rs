@@ -1,13 +1,19 @@
+mod assets;
 mod builder;
+mod data;
+mod diagrams;
 mod document;
 mod document_operations;
 mod error;
 mod feeds;
+mod filters;
+mod images;
 mod list;
+mod markdown;
 mod parse_event_handlers;
+mod shortcodes;
 mod template;
-mod filters;
-mod markdown;
+mod wiki_links;
 
 pub mod config;
 pub mod executor;
@@ -21,3 +27,4 @@ pub mod book;
 pub mod build_cache;
 pub mod search;
 pub mod sitemap;
+pub mod link_checker;
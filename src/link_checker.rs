@@ -0,0 +1,294 @@
+use lazy_static::*;
+use regex::Regex;
+
+use crate::config::Config;
+use crate::document::Document;
+use crate::error::Result;
+use crate::io_utils::{contents_of_directory, slurp};
+use crate::utils::percent_decode;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    static ref LINK: Regex = Regex::new(r#"\b(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    static ref ANCHOR: Regex =
+        Regex::new(r#"\b(?:id|name)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    static ref SCHEME: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap();
+}
+
+/// The prefixes of the markdown links that techou resolves while rendering.
+/// If they end up in the output, they could not be resolved.
+const LINK_PREFIXES: &[&str] = &["lnk::", "rel::", "id::"];
+
+/// A link in a generated file that doesn't point to a generated file or anchor
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// The generated file, relative to the output folder
+    pub file: PathBuf,
+    /// The markdown file of the document that was rendered into `file`
+    pub source: Option<String>,
+    /// The line of the link in `source`, if the link was written there and
+    /// not by a template
+    pub line: Option<usize>,
+    pub link: String,
+    pub reason: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => write!(f, "{}:{}: ", source, line)?,
+            (Some(source), None) => write!(f, "{} ({}): ", source, self.file.display())?,
+            (None, _) => write!(f, "{}: ", self.file.display())?,
+        }
+        write!(f, "{} ({})", &self.link, &self.reason)
+    }
+}
+
+/// The documents by the file they were rendered into, so that broken links
+/// can be reported with their markdown file and line
+#[derive(Default)]
+pub struct Sources<'a> {
+    documents: HashMap<PathBuf, &'a Document>,
+}
+
+impl<'a> Sources<'a> {
+    pub fn add(&mut self, document: &'a Document) {
+        let file = PathBuf::from(document.slug.trim_start_matches('/'));
+        self.documents.insert(file, document);
+    }
+}
+
+/// Check all internal `href` and `src` targets in the generated html files.
+/// Links to other files are resolved within the output folder, `#anchors`
+/// against the `id`s in the target file. External links are not checked.
+pub fn check_links(config: &Config, sources: &Sources) -> Result<Vec<BrokenLink>> {
+    let output_folder = config.folders.output_folder_path();
+    let mut files = contents_of_directory(&output_folder, "html")?;
+    files.sort();
+    let mut checker = LinkChecker {
+        output_folder: &output_folder,
+        base_url: config.project.base_url.trim_end_matches('/'),
+        anchors: HashMap::new(),
+        sources,
+    };
+    let mut broken = Vec::new();
+    for file in &files {
+        let contents = slurp(file)?;
+        broken.append(&mut checker.check(file, &contents));
+    }
+    Ok(broken)
+}
+
+/// Print the broken links with their markdown file and line
+pub fn print_report(broken: &[BrokenLink]) {
+    if broken.is_empty() {
+        println!("No broken links found");
+        return;
+    }
+    for link in broken {
+        println!("{}", &link);
+    }
+    println!("Found {} broken link(s)", broken.len());
+}
+
+struct LinkChecker<'a> {
+    output_folder: &'a Path,
+    base_url: &'a str,
+    /// The anchors of each target file, so every file is only read once
+    anchors: HashMap<PathBuf, HashSet<String>>,
+    sources: &'a Sources<'a>,
+}
+
+impl<'a> LinkChecker<'a> {
+    fn check(&mut self, file: &Path, contents: &str) -> Vec<BrokenLink> {
+        let file = file.strip_prefix(self.output_folder).unwrap_or(file);
+        let document = self.sources.documents.get(file);
+        let mut broken = Vec::new();
+        for capture in LINK.captures_iter(contents) {
+            let link = match capture.get(1).or_else(|| capture.get(2)) {
+                Some(link) => link,
+                None => continue,
+            };
+            if let Some(reason) = self.check_link(file, contents, link.as_str()) {
+                broken.push(BrokenLink {
+                    file: file.to_path_buf(),
+                    source: document.map(|document| document.filename.clone()),
+                    line: document.and_then(|document| markdown_line(document, link.as_str())),
+                    link: link.as_str().to_string(),
+                    reason,
+                });
+            }
+        }
+        broken
+    }
+
+    /// The reason why `link` is broken, or `None` if it is fine
+    /// `file` is relative to the output folder
+    fn check_link(&mut self, file: &Path, contents: &str, link: &str) -> Option<String> {
+        let link = link.trim().replace("&amp;", "&");
+        // Wiki links that could not be resolved were already reported by `WikiLinks`
+        if link.starts_with("wiki::") {
+            return None;
+        }
+        if LINK_PREFIXES.iter().any(|prefix| link.starts_with(prefix)) {
+            return Some("Unresolved techou link".to_string());
+        }
+        let link = match link.strip_prefix(self.base_url) {
+            Some(internal)
+                if !self.base_url.is_empty()
+                    && (internal.is_empty() || internal.starts_with(['/', '#', '?'])) =>
+            {
+                format!("/{}", internal.trim_start_matches('/'))
+            }
+            _ => link,
+        };
+        if link.is_empty()
+            || link.starts_with("//")
+            || link.contains("{{")
+            || SCHEME.is_match(&link)
+        {
+            return None;
+        }
+        let link = link.split('?').next().unwrap_or_default();
+        let (path, anchor) = match link.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (link, None),
+        };
+
+        let target = match path {
+            "" => self.output_folder.join(file),
+            _ => match self.resolve(file, &percent_decode(path)) {
+                Some(target) => target,
+                None => return Some("File not found".to_string()),
+            },
+        };
+
+        let anchor = match anchor {
+            Some(anchor) if !anchor.is_empty() && anchor != "top" => percent_decode(anchor),
+            _ => return None,
+        };
+        if target.extension().and_then(|e| e.to_str()) != Some("html") {
+            return None;
+        }
+        if !self.anchors.contains_key(&target) {
+            let anchors = match target == self.output_folder.join(file) {
+                true => anchors_in(contents),
+                false => anchors_in(&slurp(&target).unwrap_or_default()),
+            };
+            self.anchors.insert(target.clone(), anchors);
+        }
+        let anchors = &self.anchors[&target];
+        match anchors.contains(&anchor) {
+            true => None,
+            false => Some(format!("Anchor #{} not found", &anchor)),
+        }
+    }
+
+    /// The generated file for `path`. Absolute paths are resolved within the
+    /// output folder, relative paths next to `file`. Folders resolve to their
+    /// `index.html`
+    fn resolve(&self, file: &Path, path: &str) -> Option<PathBuf> {
+        let resolved = match path.strip_prefix('/') {
+            Some(absolute) => self.output_folder.join(absolute),
+            None => self.output_folder.join(file.parent()?).join(path),
+        };
+        if resolved.is_dir() {
+            let index = resolved.join("index.html");
+            return match index.is_file() {
+                true => Some(index),
+                false => None,
+            };
+        }
+        if resolved.is_file() {
+            return Some(resolved);
+        }
+        let html = resolved.with_extension("html");
+        match html.is_file() {
+            true => Some(html),
+            false => None,
+        }
+    }
+}
+
+/// The line of `link` in the markdown of `document`. Links written by a
+/// template or rewritten while rendering are not found
+pub(crate) fn markdown_line(document: &Document, link: &str) -> Option<usize> {
+    let link = link.replace("&amp;", "&");
+    let position = document.raw_content.find(&link)?;
+    Some(document.raw_content[..position].matches('\n').count() + 1)
+}
+
+fn anchors_in(contents: &str) -> HashSet<String> {
+    ANCHOR
+        .captures_iter(contents)
+        .filter_map(|capture| capture.get(1).or_else(|| capture.get(2)))
+        .map(|anchor| anchor.as_str().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_matter::parse_front_matter;
+
+    #[test]
+    fn test_check_links() {
+        let folder = std::env::temp_dir().join("techou-link-checker-test");
+        std::fs::create_dir_all(folder.join("posts")).unwrap();
+        std::fs::write(folder.join("posts/a.html"), "<h2 id=\"intro\">Intro</h2>").unwrap();
+        std::fs::write(folder.join("index.html"), "").unwrap();
+        let contents = r##"<a href="/posts/a.html#intro">ok</a>
+<a href="posts/a.html">ok</a>
+<a href="/">ok</a>
+<a href="https://example.org/missing.html">external</a>
+<a href="https://example.com/posts/a">internal with base url</a>
+<a href="/posts/missing.html">missing</a>
+<a href="/posts/a.html#outro">missing anchor</a>
+<a href="lnk::unknown">short link</a>
+<img src="/img/missing.png" />
+<a href="wiki::My%20Post">wiki link</a>"##;
+        let markdown = "[frontMatter]\ntitle = \"Index\"\n---\n[missing](/posts/missing.html)\n\n[short](lnk::unknown)\n";
+        let (info, _) = parse_front_matter(markdown, "index.md", &Config::default()).unwrap();
+        let document = Document::from_multiple(
+            String::new(),
+            markdown,
+            "index.html",
+            "index.md",
+            &info,
+            Vec::new(),
+            "en",
+        );
+        let mut sources = Sources::default();
+        sources.add(&document);
+        let mut checker = LinkChecker {
+            output_folder: &folder,
+            base_url: "https://example.com",
+            anchors: HashMap::new(),
+            sources: &sources,
+        };
+        let broken = checker.check(&folder.join("index.html"), contents);
+        let lines: Vec<(Option<usize>, &str)> =
+            broken.iter().map(|b| (b.line, b.link.as_str())).collect();
+        assert_eq!(
+            lines,
+            vec![
+                (Some(4), "/posts/missing.html"),
+                (None, "/posts/a.html#outro"),
+                (Some(6), "lnk::unknown"),
+                (None, "/img/missing.png"),
+            ]
+        );
+        assert_eq!(
+            broken[0].to_string(),
+            "index.md:4: /posts/missing.html (File not found)"
+        );
+        assert_eq!(
+            broken[1].to_string(),
+            "index.md (index.html): /posts/a.html#outro (Anchor #outro not found)"
+        );
+        assert_eq!(broken[0].file, PathBuf::from("index.html"));
+    }
+}
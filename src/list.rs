@@ -3,6 +3,8 @@ use serde::Serialize;
 use crate::book::Book;
 use crate::document::Document;
 
+use std::collections::BTreeMap;
+
 #[derive(Serialize, Debug)]
 pub struct Year<'a> {
     pub name: i32,
@@ -44,6 +46,8 @@ impl<'a> From<(u32, Vec<&'a Document>)> for Month<'a> {
 
 #[derive(Serialize, Debug)]
 pub struct DocumentContext<'a> {
+    /// The language of the documents that are written with this context
+    pub lang: &'a str,
     pub pages: &'a Vec<Document>,
     pub posts: &'a Vec<Document>,
     pub all_posts: &'a Vec<&'a Document>,
@@ -52,6 +56,10 @@ pub struct DocumentContext<'a> {
     pub by_tag: &'a Vec<Category<'a>>,
     pub by_keyword: &'a Vec<Category<'a>>,
     pub by_category: &'a Vec<Category<'a>>,
+    /// The user-defined taxonomies by name
+    pub by_taxonomy: &'a BTreeMap<String, Vec<Category<'a>>>,
+    /// The documents of the `[[Collections]]` by name
+    pub collections: &'a BTreeMap<String, Vec<Document>>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -59,6 +67,7 @@ pub enum ListType {
     Index,
     Category,
     Year,
+    Collection,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -76,6 +85,21 @@ pub struct Pagination {
     pub previous: Option<Page>,
 }
 
+/// One entry of a tag / keyword / category overview page
+#[derive(Serialize, Debug)]
+pub struct OverviewEntry<'a> {
+    pub name: &'a str,
+    pub count: u32,
+    /// The absolute path to the first page of the entry
+    pub path: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Overview<'a> {
+    pub title: &'a str,
+    pub entries: Vec<OverviewEntry<'a>>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct List<'a, D: AsRef<Document>>
 where
@@ -1,50 +1,80 @@
 use crate::config::ConfigRenderer;
+use crate::diagrams::DiagramRenderer;
+use crate::document::Section;
+use crate::front_matter::SectionLimit;
+use crate::images::DocumentImages;
 pub use crate::parse_event_handlers::ParseResult;
 use crate::parse_event_handlers::{
-    highlight::HighlightEventHandler, links::LinksEventHandler, section::SectionEventHandler,
+    admonitions::{preprocess_admonitions, AdmonitionsEventHandler},
+    diagrams::DiagramsEventHandler,
+    highlight::HighlightEventHandler,
+    images::ImagesEventHandler,
+    links::LinksEventHandler,
+    math::{preprocess_math, MathEventHandler},
+    section::SectionEventHandler,
     EventHandler,
 };
-use pulldown_cmark::{html, Event, Options, Parser};
+use pulldown_cmark::{html, Event, Parser};
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 // Transform the AST of the markdown to support custom markdown constructs
 pub fn markdown_to_html(
     markdown: &str,
-    section_identifier: &str,
     links: &Option<HashMap<String, String>>,
     book_html_root: Option<&str>,
     config: &ConfigRenderer,
-    limit_parsed_sections: Option<usize>,
+    limit_parsed_sections: Option<SectionLimit>,
+    images: Option<DocumentImages>,
+    diagrams: Option<&DiagramRenderer>,
 ) -> ParseResult {
     let default_hashmap: HashMap<String, String> = HashMap::new();
-    let mut opts = Options::empty();
-    if config.markdown_tables {
-        opts.insert(Options::ENABLE_TABLES);
-    }
-    if config.markdown_footnotes {
-        opts.insert(Options::ENABLE_FOOTNOTES);
-    }
+    let opts = config.markdown_options();
 
-    let parser = Parser::new_ext(markdown, opts);
+    let markdown = match config.markdown_admonitions {
+        true => preprocess_admonitions(markdown),
+        false => Cow::Borrowed(markdown),
+    };
+    let markdown = match config.markdown_math {
+        true => Cow::Owned(preprocess_math(&markdown).into_owned()),
+        false => markdown,
+    };
+    let parser = Parser::new_ext(&markdown, opts);
     let mut events: Vec<Event> = Vec::new();
     let mut result = ParseResult {
         content: String::new(),
         sections: Vec::new(),
+        images: Vec::new(),
     };
 
     let mut handlers: Vec<Box<dyn EventHandler>> = Vec::new();
 
     if config.parse_headers {
         handlers.push(Box::new(SectionEventHandler::new(
-            section_identifier,
             &config.section_header_identifier_template,
             limit_parsed_sections,
         )));
     }
 
-    if config.highlight_syntax {
-        handlers.push(Box::new(HighlightEventHandler::new(config.clone())));
+    // Before the highlighting, which would take the ```` ```math ```` blocks
+    if config.markdown_math {
+        handlers.push(Box::new(MathEventHandler::default()));
+    }
+
+    if let Some(diagrams) = diagrams {
+        if diagrams.is_enabled() {
+            handlers.push(Box::new(DiagramsEventHandler::new(diagrams)));
+        }
+    }
+
+    // Without `highlight_syntax`, it only cleans up the code block annotations
+    handlers.push(Box::new(HighlightEventHandler::new(config.clone())));
+
+    if let Some(images) = images {
+        if images.processor.is_enabled() {
+            handlers.push(Box::new(ImagesEventHandler::new(images)));
+        }
     }
 
     if config.parse_links {
@@ -58,6 +88,12 @@ pub fn markdown_to_html(
         }
     }
 
+    // First, as it holds back the start of blockquotes until it knows
+    // whether they are admonitions
+    if config.markdown_admonitions {
+        handlers.insert(0, Box::new(AdmonitionsEventHandler::default()));
+    }
+
     for event in parser {
         let mut ignore_event = false;
         for handler in handlers.iter_mut() {
@@ -71,6 +107,7 @@ pub fn markdown_to_html(
         }
     }
     html::push_html(&mut result.content, events.into_iter());
+    result.sections = Section::nested(std::mem::take(&mut result.sections));
     result
 }
 
@@ -88,7 +125,7 @@ mod tests {
             parse_headers: true,
             section_header_identifier_template: "".to_owned(),
             store_build_cache: false,
-            swift_use_splash: false,
+            ..ConfigRenderer::default()
         };
         let contents = r#"
 # Section 1
@@ -97,9 +134,11 @@ Hello world
 More text
 ## Another section
 # Final section"#;
-        let result = markdown_to_html(&contents, "", &None, None, &cfg);
-        assert_eq!(result.sections.len(), 4);
-        assert_eq!(result.sections[0].1, "Section 1");
+        let result = markdown_to_html(&contents, &None, None, &cfg, None, None, None);
+        assert_eq!(result.sections.len(), 2);
+        assert_eq!(result.sections[0].title, "Section 1");
+        assert_eq!(result.sections[0].children.len(), 2);
+        assert_eq!(result.sections[1].title, "Final section");
     }
 
     #[test]
@@ -118,10 +157,12 @@ if let Some(x) = variable {
 }
 
 "#;
-        let result = markdown_to_html(&contents, "", &None, None, &cfg);
+        let result = markdown_to_html(&contents, &None, None, &cfg, None, None, None);
         // Test for the CSS classes
-        println!("{}", result.content);
-        assert!(result.content.contains("techoucontrol techourust"));
+        assert!(result.content.contains(r#"<pre class="Rust">"#));
+        assert!(result
+            .content
+            .contains(r#"<span class="keyword control rust">if</span>"#));
     }
 
     #[test]
@@ -132,9 +173,9 @@ if let Some(x) = variable {
         let contents = r#"
 [bonjour](rel::posts/post.md)
 "#;
-        let result = markdown_to_html(&contents, "", &None, Some("book"), &cfg);
+        let result = markdown_to_html(&contents, &None, Some("book"), &cfg, None, None, None);
         assert!(result.content.contains("/book/posts/post.html"));
-        let result = markdown_to_html(&contents, "", &None, None, &cfg);
+        let result = markdown_to_html(&contents, &None, None, &cfg, None, None, None);
         assert!(result.content.contains("/posts/post.html"));
     }
 
@@ -156,9 +197,11 @@ yep
             .iter()
             .map(|(a, b)| (a.to_string(), b.to_string()))
             .collect();
-        let result = markdown_to_html(&contents, "", &Some(reflinks), None, &cfg);
-        // Test for the CSS classes
-        println!("{}", &result.content);
-        assert!(result.content.contains("hello"));
+        let result = markdown_to_html(&contents, &Some(reflinks), None, &cfg, None, None, None);
+        assert!(result.content.contains(r#"<a href="jojo""#));
+        assert!(result.content.contains(r#"<a href="jaja""#));
+        assert!(result
+            .content
+            .contains(r#"<a href="http://example.com">non-link</a>"#));
     }
 }
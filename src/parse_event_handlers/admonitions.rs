@@ -0,0 +1,248 @@
+use super::*;
+
+use lazy_static::*;
+use pulldown_cmark::CowStr;
+use regex::Regex;
+
+use crate::utils::{escape_xml, fenced_code_blocks};
+
+use std::borrow::Cow;
+
+lazy_static! {
+    static ref MARKER: Regex = Regex::new(r"^\s*\[!([A-Za-z]+)\]").unwrap();
+    static ref CONTAINER: Regex = Regex::new(r"^\s*:::\s*([A-Za-z]+)?(.*)$").unwrap();
+}
+
+/// Turn `::: warning Optional title` ... `:::` containers into the GitHub style
+/// `> [!WARNING] Optional title` blockquotes, which `AdmonitionsEventHandler`
+/// renders. Containers can be nested, code blocks are kept as they are.
+pub fn preprocess_admonitions<'a>(markdown: &'a str) -> Cow<'a, str> {
+    if !markdown.contains(":::") {
+        return Cow::Borrowed(markdown);
+    }
+    let code_blocks = fenced_code_blocks(markdown);
+    let mut output = String::with_capacity(markdown.len());
+    let mut depth = 0;
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        let in_code = code_blocks.iter().any(|block| block.contains(&offset));
+        offset += line.len();
+        let prefix = "> ".repeat(depth);
+        let container = match in_code {
+            true => None,
+            false => CONTAINER.captures(line.trim_end()),
+        };
+        match container {
+            Some(captures) => match captures.get(1) {
+                Some(kind) => {
+                    let marker =
+                        format!("[!{}] {}", kind.as_str().to_uppercase(), captures[2].trim());
+                    output.push_str(&prefix);
+                    output.push_str("> ");
+                    output.push_str(marker.trim_end());
+                    output.push('\n');
+                    depth += 1;
+                }
+                // A closing `:::` ends the blockquote with an empty line, so that
+                // the following text doesn't continue its paragraph
+                None if depth > 0 && captures[2].trim().is_empty() => {
+                    depth -= 1;
+                    output.push_str("> ".repeat(depth).trim_end());
+                    output.push('\n');
+                }
+                None => {
+                    output.push_str(&prefix);
+                    output.push_str(line);
+                }
+            },
+            None if line.trim().is_empty() => {
+                output.push_str(prefix.trim_end());
+                output.push('\n');
+            }
+            None => {
+                output.push_str(&prefix);
+                output.push_str(line);
+            }
+        }
+    }
+    Cow::Owned(output)
+}
+
+/// A blockquote until its first line shows whether it is an admonition
+struct PendingBlockquote {
+    events: Vec<Event<'static>>,
+    text: String,
+}
+
+/// Renders `> [!NOTE]`, `> [!TIP]`, `> [!WARNING]` (or any other kind) blockquotes
+/// as `<aside class="admonition note">` with a title. The title is the text after
+/// the marker or the kind.
+#[derive(Default)]
+pub struct AdmonitionsEventHandler {
+    pending: Option<PendingBlockquote>,
+    /// For each open blockquote, whether it is an admonition
+    blockquotes: Vec<bool>,
+}
+
+impl AdmonitionsEventHandler {
+    fn handle_event(&mut self, event: &Event, events: &mut Vec<Event>) -> bool {
+        match event {
+            Event::Start(Tag::BlockQuote) => {
+                self.pending = Some(PendingBlockquote {
+                    events: vec![Event::Start(Tag::BlockQuote)],
+                    text: String::new(),
+                });
+                false
+            }
+            Event::End(Tag::BlockQuote) => match self.blockquotes.pop() {
+                Some(true) => {
+                    events.push(Event::Html(CowStr::Borrowed("</aside>\n")));
+                    false
+                }
+                _ => true,
+            },
+            _ => true,
+        }
+    }
+
+    /// Collect the first line of the pending blockquote. Returns `None` as long
+    /// as the line continues
+    fn collect(&mut self, event: &Event) -> Option<PendingBlockquote> {
+        let pending = self.pending.as_mut()?;
+        match event {
+            Event::Start(Tag::Paragraph) if pending.events.len() == 1 => {
+                pending.events.push(Event::Start(Tag::Paragraph));
+                None
+            }
+            Event::Text(text) if pending.events.len() > 1 => {
+                pending.text.push_str(text);
+                pending.events.push(Event::Text(CowStr::Boxed(
+                    text.to_string().into_boxed_str(),
+                )));
+                None
+            }
+            _ => self.pending.take(),
+        }
+    }
+}
+
+impl EventHandler for AdmonitionsEventHandler {
+    fn handle(
+        &mut self,
+        event: &Event,
+        _result: &mut ParseResult,
+        events: &mut Vec<Event>,
+    ) -> bool {
+        if self.pending.is_none() {
+            return self.handle_event(event, events);
+        }
+        let pending = match self.collect(event) {
+            Some(pending) => pending,
+            None => return false,
+        };
+        let captures = match pending.events.len() > 1 {
+            true => MARKER.captures(&pending.text),
+            false => None,
+        };
+        let captures = match captures {
+            Some(captures) => captures,
+            None => {
+                self.blockquotes.push(false);
+                events.extend(pending.events);
+                return self.handle_event(event, events);
+            }
+        };
+        let kind = captures[1].to_lowercase();
+        let rest = pending.text[captures[0].len()..].trim();
+        let line_ended = matches!(
+            event,
+            Event::SoftBreak | Event::HardBreak | Event::End(Tag::Paragraph)
+        );
+        let title = match line_ended && !rest.is_empty() {
+            true => rest.to_string(),
+            false => capitalized(&kind),
+        };
+        events.push(Event::Html(CowStr::Boxed(
+            format!(
+                "<aside class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+                escape_xml(&kind),
+                escape_xml(&title)
+            )
+            .into_boxed_str(),
+        )));
+        self.blockquotes.push(true);
+        match event {
+            // The paragraph only contained the marker
+            Event::End(Tag::Paragraph) => false,
+            Event::SoftBreak | Event::HardBreak => {
+                events.push(Event::Start(Tag::Paragraph));
+                false
+            }
+            // The first line continues with other inline elements
+            _ => {
+                events.push(Event::Start(Tag::Paragraph));
+                if !rest.is_empty() {
+                    events.push(Event::Text(CowStr::Boxed(
+                        format!("{} ", rest).into_boxed_str(),
+                    )));
+                }
+                self.handle_event(event, events)
+            }
+        }
+    }
+}
+
+/// The first line of an admonition without its `[!KIND]` marker (e.g. for the search)
+pub fn strip_marker(text: &str) -> &str {
+    match MARKER.find(text) {
+        Some(marker) => text[marker.end()..].trim_start(),
+        None => text,
+    }
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigRenderer;
+    use crate::markdown::markdown_to_html;
+
+    #[test]
+    fn test_preprocess_admonitions() {
+        let markdown =
+            "::: warning Be careful\nText\n\n::: tip\nNested\n:::\n:::\nAfter\n\n```\n:::\n```\n";
+        assert_eq!(
+            preprocess_admonitions(markdown),
+            "> [!WARNING] Be careful\n> Text\n>\n> > [!TIP]\n> > Nested\n>\n\nAfter\n\n```\n:::\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_marker() {
+        assert_eq!(strip_marker("[!WARNING] Be careful"), "Be careful");
+        assert_eq!(strip_marker("[!NOTE]"), "");
+        assert_eq!(strip_marker("A [!NOTE]"), "A [!NOTE]");
+    }
+
+    #[test]
+    fn test_admonitions() {
+        let config = ConfigRenderer::default();
+        let markdown = "> [!NOTE]\n> Some *text*\n\n> [!WARNING] Be careful\n> Really\n\n> A quote\n\n::: tip\nA tip\n:::\n";
+        let html = markdown_to_html(markdown, &None, None, &config, None, None, None).content;
+        assert!(html.contains(
+            "<aside class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n<p>Some <em>text</em></p>\n</aside>"
+        ));
+        assert!(html.contains(
+            "<aside class=\"admonition warning\">\n<p class=\"admonition-title\">Be careful</p>\n<p>Really</p>\n</aside>"
+        ));
+        assert!(html.contains("<blockquote>\n<p>A quote</p>\n</blockquote>"));
+        assert!(html.contains("<p class=\"admonition-title\">Tip</p>\n<p>A tip</p>\n</aside>"));
+    }
+}
@@ -0,0 +1,68 @@
+use super::*;
+
+use pulldown_cmark::{CodeBlockKind, CowStr};
+
+use crate::diagrams::{DiagramKind, DiagramRenderer};
+use crate::utils::escape_xml;
+
+/// Replaces ```` ```svgbob ```` and ```` ```dot ```` code blocks with inline svg
+pub struct DiagramsEventHandler<'a> {
+    renderer: &'a DiagramRenderer,
+    current: Option<(DiagramKind, String)>,
+}
+
+impl<'a> DiagramsEventHandler<'a> {
+    pub fn new(renderer: &'a DiagramRenderer) -> DiagramsEventHandler<'a> {
+        DiagramsEventHandler {
+            renderer,
+            current: None,
+        }
+    }
+}
+
+impl<'a> EventHandler for DiagramsEventHandler<'a> {
+    fn handle(
+        &mut self,
+        event: &Event,
+        _result: &mut ParseResult,
+        events: &mut Vec<Event>,
+    ) -> bool {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let language = info.split([',', ' ']).next().unwrap_or_default();
+                match DiagramKind::from_language(language) {
+                    Some(kind) => {
+                        self.current = Some((kind, String::new()));
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Event::Text(text) if self.current.is_some() => {
+                if let Some((_, source)) = self.current.as_mut() {
+                    source.push_str(text);
+                }
+                false
+            }
+            Event::End(Tag::CodeBlock(_)) if self.current.is_some() => {
+                if let Some((kind, source)) = self.current.take() {
+                    // Diagrams that can't be rendered are shown as their source
+                    let html = match self.renderer.render(kind, &source) {
+                        Ok(svg) => format!(
+                            "<figure class=\"diagram diagram-{}\">{}</figure>",
+                            kind.name(),
+                            svg
+                        ),
+                        Err(e) => {
+                            println!("{}", &e);
+                            format!("<pre><code>{}</code></pre>", escape_xml(&source))
+                        }
+                    };
+                    events.push(Event::Html(CowStr::Boxed(html.into_boxed_str())));
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+}
@@ -1,83 +1,492 @@
-use rayon;
-use std::process::Command;
-use syntect::html::ClassedHTMLGenerator;
-use syntect::parsing::SyntaxSet;
+use lazy_static::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, Theme, ThemeSet};
+use syntect::html::{
+    css_for_theme_with_class_style, styled_line_to_highlighted_html, ClassStyle, IncludeBackground,
+};
+use syntect::parsing::{
+    BasicScopeStackOp, ParseState, Scope, ScopeStack, SyntaxDefinition, SyntaxReference, SyntaxSet,
+};
 use syntect::util::LinesWithEndings;
 
 use super::*;
 
-use crate::config::ConfigRenderer;
+use crate::config::{Config, ConfigRenderer, HighlightStyle};
+use crate::error::{Result, TechouError};
+use crate::utils::escape_xml;
+
+lazy_static! {
+    static ref THEMES: ThemeSet = ThemeSet::load_defaults();
+    static ref THEME_FILES: Mutex<HashMap<PathBuf, Theme>> = Mutex::new(HashMap::new());
+    static ref SYNTAX_SETS: Mutex<HashMap<PathBuf, Arc<SyntaxSet>>> = Mutex::new(HashMap::new());
+    // syntect needs a `'static` prefix for the css classes
+    static ref PREFIXES: Mutex<HashMap<String, &'static str>> = Mutex::new(HashMap::new());
+    static ref CODE_BLOCK_ATTRIBUTE: Regex = Regex::new(
+        r#"(?P<key>[\w-]+)\s*=\s*(?:"(?P<quoted>[^"]*)"|(?P<value>\d[\d\s-]*\d|[^,\s"]+))|(?P<flag>[^,\s=]+)"#
+    )
+    .unwrap();
+}
+
+/// The `highlight_theme`: A bundled theme or a `.tmTheme` file in the project
+pub fn load_theme(config: &ConfigRenderer) -> Result<Theme> {
+    if let Some(theme) = THEMES.themes.get(&config.highlight_theme) {
+        return Ok(theme.clone());
+    }
+    let path = config.project_root.join(&config.highlight_theme);
+    let mut files = THEME_FILES.lock().unwrap();
+    if let Some(theme) = files.get(&path) {
+        return Ok(theme.clone());
+    }
+    let theme = ThemeSet::get_theme(&path).map_err(|e| TechouError::Other {
+        issue: format!("{:?}: Could not load highlight theme: {}", &path, e),
+    })?;
+    files.insert(path, theme.clone());
+    Ok(theme)
+}
+
+/// Grammars for languages that syntect doesn't bundle
+const EMBEDDED_SYNTAXES: &[&str] = &[include_str!("../syntaxes/Swift.sublime-syntax")];
+
+/// The bundled and embedded syntaxes and the `.sublime-syntax` grammars in the
+/// `syntaxes_folder`. Grammars that can't be loaded are reported.
+pub fn load_syntax_set(config: &ConfigRenderer) -> Arc<SyntaxSet> {
+    let folder = config.project_root.join(&config.syntaxes_folder);
+    let mut sets = SYNTAX_SETS.lock().unwrap();
+    if let Some(set) = sets.get(&folder) {
+        return set.clone();
+    }
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for syntax in EMBEDDED_SYNTAXES {
+        match SyntaxDefinition::load_from_str(syntax, true, None) {
+            Ok(definition) => builder.add(definition),
+            Err(e) => println!("Could not load an embedded syntax: {}", e),
+        }
+    }
+    if !config.syntaxes_folder.is_empty() && folder.is_dir() {
+        if let Err(e) = builder.add_from_folder(&folder, true) {
+            println!("{:?}: Could not load syntaxes: {}", &folder, e);
+        }
+    }
+    let set = Arc::new(builder.build());
+    sets.insert(folder, set.clone());
+    set
+}
+
+/// Write the css for the `highlight_theme` into the output folder, if code
+/// is highlighted with css classes
+pub fn write_theme_css(config: &Config) -> Result<()> {
+    let render = &config.render;
+    if !render.highlight_syntax
+        || render.highlight_style != HighlightStyle::Classes
+        || render.highlight_css_file.is_empty()
+    {
+        return Ok(());
+    }
+    let theme = load_theme(render)?;
+    let css = css_for_theme_with_class_style(&theme, class_style(&render.highlight_prefix));
+    crate::io_utils::spit(
+        config
+            .folders
+            .output_folder_path()
+            .join(&render.highlight_css_file),
+        &css,
+    )
+}
+
+fn class_style(prefix: &str) -> ClassStyle {
+    if prefix.is_empty() {
+        return ClassStyle::Spaced;
+    }
+    let mut prefixes = PREFIXES.lock().unwrap();
+    let prefix = prefixes
+        .entry(prefix.to_string())
+        .or_insert_with(|| Box::leak(format!("{}-", prefix).into_boxed_str()));
+    ClassStyle::SpacedPrefixed { prefix }
+}
+
+fn css_color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// The attributes of a fenced code block after the language, e.g.
+/// ```` ```rust,linenos,hl_lines=3-5 8,title="main.rs",copy,diff ````
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlockInfo {
+    pub language: String,
+    /// `linenos`
+    pub line_numbers: bool,
+    /// `linenostart=10`
+    pub line_number_start: usize,
+    /// `hl_lines=3-5 8`, in displayed line numbers
+    pub highlighted_lines: Vec<RangeInclusive<usize>>,
+    /// `title="main.rs"`, a caption above the code
+    pub title: Option<String>,
+    /// `copy`, marks the code for a copy button (`data-copy`)
+    pub copy: bool,
+    /// `diff`, lines starting with `+` or `-` are marked as added or removed
+    pub diff: bool,
+}
+
+impl CodeBlockInfo {
+    pub fn parse(info: &str) -> CodeBlockInfo {
+        let mut block = CodeBlockInfo {
+            language: "text".to_string(),
+            line_numbers: false,
+            line_number_start: 1,
+            highlighted_lines: Vec::new(),
+            title: None,
+            copy: false,
+            diff: false,
+        };
+        for (index, capture) in CODE_BLOCK_ATTRIBUTE.captures_iter(info).enumerate() {
+            if let Some(flag) = capture.name("flag") {
+                match flag.as_str() {
+                    "linenos" => block.line_numbers = true,
+                    "copy" => block.copy = true,
+                    // ```diff is the language
+                    "diff" if index > 0 => block.diff = true,
+                    language if index == 0 => block.language = language.to_string(),
+                    _ => (),
+                }
+                continue;
+            }
+            let key = capture.name("key").map(|m| m.as_str()).unwrap_or_default();
+            let value = capture
+                .name("quoted")
+                .or_else(|| capture.name("value"))
+                .map(|m| m.as_str())
+                .unwrap_or_default();
+            match key {
+                "hl_lines" => block.highlighted_lines = line_ranges(value),
+                "linenostart" => block.line_number_start = value.parse().unwrap_or(1),
+                "title" => block.title = Some(value.to_string()),
+                _ => (),
+            }
+        }
+        block
+    }
+
+    /// Line numbers, highlighted lines and diffs need one element per line
+    fn is_annotated(&self) -> bool {
+        self.line_numbers || self.diff || !self.highlighted_lines.is_empty()
+    }
+
+    fn is_highlighted(&self, line_number: usize) -> bool {
+        self.highlighted_lines
+            .iter()
+            .any(|range| range.contains(&line_number))
+    }
+}
+
+/// `3-5 8` or `3-5,8`
+fn line_ranges(value: &str) -> Vec<RangeInclusive<usize>> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|range| match range.split_once('-') {
+            Some((start, end)) => Some(start.trim().parse().ok()?..=end.trim().parse().ok()?),
+            None => {
+                let line = range.trim().parse().ok()?;
+                Some(line..=line)
+            }
+        })
+        .collect()
+}
+
+/// The classes for `scope`, as the `ClassedHTMLGenerator` writes them
+fn scope_classes(scope: Scope, style: ClassStyle) -> String {
+    let prefix = match style {
+        ClassStyle::SpacedPrefixed { prefix } => prefix,
+        _ => "",
+    };
+    scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", prefix, atom))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The highlighted html of a line without the line break
+fn without_newline(mut html: String) -> String {
+    if let Some(index) = html.rfind('\n') {
+        html.remove(index);
+    }
+    html
+}
 
 pub struct HighlightEventHandler {
-    next_text_is_code: bool,
-    language: String,
+    code_block: Option<CodeBlockInfo>,
     current_code: String,
-    syntax_set: SyntaxSet,
+    syntax_set: Arc<SyntaxSet>,
+    /// Only for inline styles
+    theme: Option<Theme>,
     config_renderer: ConfigRenderer,
 }
 
 impl HighlightEventHandler {
     pub fn new(config_renderer: ConfigRenderer) -> HighlightEventHandler {
-        let ps = SyntaxSet::load_defaults_newlines();
+        let syntax_set = load_syntax_set(&config_renderer);
+        let theme = match config_renderer.highlight_style {
+            HighlightStyle::Inline if config_renderer.highlight_syntax => {
+                match load_theme(&config_renderer) {
+                    Ok(theme) => Some(theme),
+                    Err(e) => {
+                        println!("{}. Falling back to css classes", &e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
         HighlightEventHandler {
-            next_text_is_code: false,
-            language: "text".to_owned(),
+            code_block: None,
             current_code: String::new(),
-            syntax_set: ps,
+            syntax_set,
+            theme,
             config_renderer,
         }
     }
 
-    fn non_swift_code(&self) -> (String, String) {
-        let mut lng = self.language.clone();
-        if lng == "Swift" || lng == "swift" {
-            lng = "Rust".to_string();
+    /// The `class` and `style` attributes of the `pre` around the code
+    fn pre_attributes(&self, syntax_name: &str) -> String {
+        match &self.theme {
+            Some(theme) => {
+                let mut style = String::new();
+                if let Some(background) = theme.settings.background {
+                    style.push_str(&format!("background-color:{};", css_color(background)));
+                }
+                if let Some(foreground) = theme.settings.foreground {
+                    style.push_str(&format!("color:{};", css_color(foreground)));
+                }
+                format!("class=\"{}\" style=\"{}\"", syntax_name, style)
+            }
+            None => {
+                // Without a prefix, the `pre` keeps the markup of earlier versions
+                let prefix = &self.config_renderer.highlight_prefix;
+                match prefix.is_empty() {
+                    true => format!("class=\"{}\"", syntax_name),
+                    false => format!("class=\"{}-code {}\"", prefix, syntax_name),
+                }
+            }
         }
-        let syntax = match self.syntax_set.find_syntax_by_name(&lng) {
+    }
+
+    fn inline_lines(&self, code: &str, syntax: &SyntaxReference, theme: &Theme) -> Vec<String> {
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        LinesWithEndings::from(code)
+            .map(|line| {
+                let regions = highlighter.highlight(line, &self.syntax_set);
+                without_newline(styled_line_to_highlighted_html(
+                    &regions[..],
+                    IncludeBackground::No,
+                ))
+            })
+            .collect()
+    }
+
+    /// Each line is closed on its own, so that lines can be wrapped. The scopes that
+    /// span multiple lines are opened again in the next line.
+    fn classed_lines(&self, code: &str, syntax: &SyntaxReference) -> Vec<String> {
+        let style = class_style(&self.config_renderer.highlight_prefix);
+        let mut parse_state = ParseState::new(syntax);
+        let mut scopes = ScopeStack::new();
+        LinesWithEndings::from(code)
+            .map(|line| {
+                let ops = parse_state.parse_line(line, &self.syntax_set);
+                let mut html: String = scopes
+                    .as_slice()
+                    .iter()
+                    .map(|scope| format!("<span class=\"{}\">", scope_classes(*scope, style)))
+                    .collect();
+                let mut index = 0;
+                for (position, op) in ops {
+                    if position > index {
+                        html.push_str(&escape_xml(&line[index..position]));
+                        index = position;
+                    }
+                    scopes.apply_with_hook(&op, |op, _| match op {
+                        BasicScopeStackOp::Push(scope) => html
+                            .push_str(&format!("<span class=\"{}\">", scope_classes(scope, style))),
+                        BasicScopeStackOp::Pop => html.push_str("</span>"),
+                    });
+                }
+                html.push_str(&escape_xml(&line[index..]));
+                html.push_str(&"</span>".repeat(scopes.as_slice().len()));
+                without_newline(html)
+            })
+            .collect()
+    }
+
+    fn syntect_code(&self, language: &str, code: &str) -> (String, Vec<String>) {
+        let syntax = match self.syntax_set.find_syntax_by_token(language) {
             Some(s) => s,
-            None => match self.syntax_set.find_syntax_by_extension(&lng) {
-                Some(s) => s,
-                None => self.syntax_set.find_syntax_plain_text(),
-            },
+            None => self.syntax_set.find_syntax_plain_text(),
         };
 
-        let mut html_generator = ClassedHTMLGenerator::new_with_class_style(
-            syntax,
-            &self.syntax_set,
-            syntect::html::ClassStyle::SpacedPrefixed { prefix: "" },
-        );
-        let lines = LinesWithEndings::from(&self.current_code);
-        for line in lines {
-            html_generator.parse_html_for_line_which_includes_newline(line);
+        let lines = match &self.theme {
+            Some(theme) => self.inline_lines(code, syntax, theme),
+            None => self.classed_lines(code, syntax),
+        };
+        (syntax.name.clone(), lines)
+    }
+
+    /// Highlight the whole block with an `external_highlighters` command, which gets
+    /// the code on stdin and writes html to stdout. `None` if the command failed.
+    fn external_code(&self, command: &str, code: &str) -> Option<Vec<String>> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next()?;
+        let output = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                // Write from another thread, so that a full stdout pipe can't block us
+                let writer = child.stdin.take().map(|mut stdin| {
+                    let code = code.to_string();
+                    std::thread::spawn(move || stdin.write_all(code.as_bytes()))
+                });
+                let output = child.wait_with_output()?;
+                if let Some(writer) = writer {
+                    writer.join().unwrap_or(Ok(()))?;
+                }
+                Ok(output)
+            });
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                println!("Could not run highlighter `{}`: {}", command, e);
+                return None;
+            }
+        };
+        if !output.status.success() {
+            println!(
+                "Highlighter `{}` failed: {}",
+                command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        match String::from_utf8(output.stdout) {
+            Ok(html) => Some(html.lines().map(|line| line.to_string()).collect()),
+            Err(_) => {
+                println!("Highlighter `{}` returned invalid UTF-8", command);
+                None
+            }
         }
-        (syntax.name.clone(), html_generator.finalize())
     }
 
-    fn swift_code(&self) -> String {
-        if !self.config_renderer.swift_use_splash {
-            return self.non_swift_code().1;
+    /// A css class with the `highlight_prefix`
+    fn class(&self, name: &str) -> String {
+        match self.config_renderer.highlight_prefix.as_str() {
+            "" => name.to_string(),
+            prefix => format!("{}-{}", prefix, name),
         }
-        use rayon::prelude::*;
-        // it seems splash works line-based, so we just highlight by line
-        let lines: Vec<String> = self
-            .current_code
-            .par_lines()
-            .map(|l| {
-                let mapped = l.replace("\n", "").replace("\"", "\\\"");
-
-                let output = Command::new("/usr/local/bin/SplashHTMLGen")
-                    .arg(mapped)
-                    .output()
-                    .expect("Please install Splash / SplashHTMLGen in /usr/local/bin")
-                    .stdout;
-                match String::from_utf8(output) {
-                    Ok(n) => n.replace("class=\"", "class=\"swift-"),
-                    Err(_) => l.to_owned(),
-                }
-            })
-            .collect();
+    }
+
+    /// The html for the collected code of the block
+    fn render(&self, block: &CodeBlockInfo) -> String {
+        // In diff mode, the `+` and `-` are not part of the code
+        let mut markers: Vec<Option<char>> = Vec::new();
+        let mut code = String::with_capacity(self.current_code.len());
+        for line in LinesWithEndings::from(&self.current_code) {
+            let marker = line
+                .chars()
+                .next()
+                .filter(|c| block.diff && (*c == '+' || *c == '-'));
+            markers.push(marker);
+            code.push_str(match marker {
+                Some(_) => &line[1..],
+                None => line,
+            });
+        }
+
+        let external = self
+            .config_renderer
+            .external_highlighters
+            .get(&block.language)
+            .map(|command| self.external_code(command, &code));
+        let (syntax_name, lines) = match external {
+            Some(Some(lines)) => (block.language.clone(), lines),
+            // A failed highlighter falls back to plain text
+            Some(None) => (
+                "Plain Text".to_string(),
+                code.lines().map(escape_xml).collect(),
+            ),
+            None => self.syntect_code(&block.language, &code),
+        };
+
+        let html = match block.is_annotated() {
+            false => lines.join("\n"),
+            true => lines
+                .iter()
+                .enumerate()
+                .map(|(index, line)| {
+                    let number = block.line_number_start + index;
+                    let mut classes = vec![self.class("line")];
+                    if block.is_highlighted(number) {
+                        classes.push(self.class("highlighted"));
+                    }
+                    let marker = match markers.get(index).copied().flatten() {
+                        Some(marker) => {
+                            classes.push(self.class(match marker {
+                                '+' => "diff-add",
+                                _ => "diff-remove",
+                            }));
+                            format!(
+                                "<span class=\"{}\">{}</span>",
+                                self.class("diff-marker"),
+                                marker
+                            )
+                        }
+                        None => String::new(),
+                    };
+                    let number = match block.line_numbers {
+                        true => format!(
+                            "<span class=\"{}\">{}</span>",
+                            self.class("line-number"),
+                            number
+                        ),
+                        false => String::new(),
+                    };
+                    format!(
+                        "<span class=\"{}\">{}{}{}</span>",
+                        classes.join(" "),
+                        number,
+                        marker,
+                        line
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
 
-        lines.join("\n")
+        let copy = match block.copy {
+            true => " data-copy",
+            false => "",
+        };
+        let pre = format!(
+            "<pre {}{}><code>{}</code></pre>",
+            self.pre_attributes(&syntax_name),
+            copy,
+            &html
+        );
+        match &block.title {
+            Some(title) => format!(
+                "<figure class=\"code-block\"><figcaption>{}</figcaption>{}</figure>",
+                escape_xml(title),
+                pre
+            ),
+            None => pre,
+        }
     }
 }
 
@@ -89,33 +498,198 @@ impl EventHandler for HighlightEventHandler {
         events: &mut Vec<Event>,
     ) -> bool {
         match event {
-            Event::Start(Tag::CodeBlock(pulldown_cmark::CodeBlockKind::Fenced(ref lang))) => {
-                self.next_text_is_code = true;
-                self.language = lang.to_string();
-                return false;
+            // Without highlighting, only the language of the annotations is kept
+            // for the `language-*` class
+            Event::Start(Tag::CodeBlock(pulldown_cmark::CodeBlockKind::Fenced(ref info)))
+                if !self.config_renderer.highlight_syntax =>
+            {
+                let language = info.split([',', ' ']).next().unwrap_or_default();
+                if language.len() == info.len() {
+                    return true;
+                }
+                events.push(Event::Start(Tag::CodeBlock(
+                    pulldown_cmark::CodeBlockKind::Fenced(pulldown_cmark::CowStr::Boxed(
+                        language.to_string().into_boxed_str(),
+                    )),
+                )));
+                false
             }
-            Event::Text(ref text) if self.next_text_is_code => {
+            Event::Start(Tag::CodeBlock(pulldown_cmark::CodeBlockKind::Fenced(ref info))) => {
+                self.code_block = Some(CodeBlockInfo::parse(info));
+                false
+            }
+            Event::Text(ref text) if self.code_block.is_some() => {
                 self.current_code.push_str(text);
-                return false;
+                false
             }
-            Event::End(Tag::CodeBlock(_)) => {
-                let (syntax_name, html_str) = match self.language.as_str() {
-                    "Swift" | "swift" => ("Swift".to_owned(), self.swift_code()),
-                    _ => self.non_swift_code(),
-                };
-                events.push(Event::Html(pulldown_cmark::CowStr::Boxed(
-                    format!(
-                        "<pre class=\"{}\"><code>{}</code></pre>",
-                        &syntax_name, &html_str
-                    )
-                    .into_boxed_str(),
-                )));
+            Event::End(Tag::CodeBlock(_)) if self.code_block.is_some() => {
+                if let Some(block) = self.code_block.take() {
+                    events.push(Event::Html(pulldown_cmark::CowStr::Boxed(
+                        self.render(&block).into_boxed_str(),
+                    )));
+                }
                 self.current_code = String::new();
-                self.language = "text".to_owned();
-                self.next_text_is_code = false;
+                // The start of the block was swallowed as well
+                false
             }
-            _ => (),
+            _ => true,
         }
-        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight(config: ConfigRenderer) -> String {
+        highlight_markdown(config, "```rust\nfn main() {}\n```\n")
+    }
+
+    fn highlight_markdown(config: ConfigRenderer, markdown: &str) -> String {
+        let mut handler = HighlightEventHandler::new(config);
+        let mut result = ParseResult {
+            content: String::new(),
+            sections: Vec::new(),
+            images: Vec::new(),
+        };
+        let mut events = Vec::new();
+        let parser = pulldown_cmark::Parser::new(markdown);
+        for event in parser {
+            if handler.handle(&event, &mut result, &mut events) {
+                events.push(event);
+            }
+        }
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events.into_iter());
+        html
+    }
+
+    #[test]
+    fn test_highlight_styles() {
+        let mut config = ConfigRenderer::default();
+        config.highlight_prefix = "hl".to_string();
+        let html = highlight(config.clone());
+        assert!(html.contains(r#"<pre class="hl-code Rust">"#));
+        assert!(html.contains(r#"<span class="hl-source hl-rust">"#));
+
+        config.highlight_style = HighlightStyle::Inline;
+        let html = highlight(config);
+        assert!(html.contains(r#"<pre class="Rust" style="background-color:#ffffff;"#));
+        assert!(html.contains("<span style=\"color:"));
+    }
+
+    #[test]
+    fn test_theme_css() {
+        let config = ConfigRenderer::default();
+        let theme = load_theme(&config).unwrap();
+        let css = css_for_theme_with_class_style(&theme, class_style("techou"));
+        assert!(css.contains(".techou-code"));
+    }
+
+    #[test]
+    fn test_code_block_info() {
+        let block = CodeBlockInfo::parse(r#"rust,linenos,hl_lines=3-5 8,title="main.rs",copy"#);
+        assert_eq!(block.language, "rust");
+        assert!(block.line_numbers);
+        assert_eq!(block.highlighted_lines, vec![3..=5, 8..=8]);
+        assert_eq!(block.title.as_deref(), Some("main.rs"));
+        assert!(block.copy);
+        assert!(!block.diff);
+
+        let block = CodeBlockInfo::parse("rust linenostart=10 diff");
+        assert_eq!(block.line_number_start, 10);
+        assert!(block.diff);
+        assert_eq!(CodeBlockInfo::parse("diff").language, "diff");
+        assert!(!CodeBlockInfo::parse("diff").diff);
+        assert_eq!(CodeBlockInfo::parse("").language, "text");
+    }
+
+    #[test]
+    fn test_annotated_code() {
+        let markdown = r#"```rust,linenos,hl_lines=2,title="main.rs",diff
+/* a
+comment */
+-let a = 1;
++let a = 2;
+```
+
+    indented
+"#;
+        let html = highlight_markdown(ConfigRenderer::default(), markdown);
+        assert!(html.starts_with("<figure class=\"code-block\"><figcaption>main.rs</figcaption>"));
+        assert!(html.contains("<span class=\"line\"><span class=\"line-number\">1</span>"));
+        assert!(
+            html.contains("<span class=\"line highlighted\"><span class=\"line-number\">2</span>")
+        );
+        assert!(html.contains(
+            "<span class=\"line diff-remove\"><span class=\"line-number\">3</span><span class=\"diff-marker\">-</span>"
+        ));
+        // Every line closes its own spans
+        for line in html
+            .lines()
+            .filter(|line| line.starts_with("<span class=\"line"))
+        {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+        // No stray end of the code block
+        assert_eq!(html.matches("</pre>").count(), 2);
+    }
+
+    #[test]
+    fn test_annotated_code_options() {
+        let markdown = "```rust,linenos,hl_lines=1\nlet a = 1;\n```\n";
+        let mut config = ConfigRenderer::default();
+        config.highlight_prefix = "hl".to_string();
+        let html = highlight_markdown(config.clone(), markdown);
+        assert!(html.contains(
+            "<span class=\"hl-line hl-highlighted\"><span class=\"hl-line-number\">1</span>"
+        ));
+        // Without highlighting, only the language is kept
+        config.highlight_syntax = false;
+        let html = highlight_markdown(config, markdown);
+        assert!(html.starts_with("<pre><code class=\"language-rust\">let a = 1;"));
+    }
+
+    #[test]
+    fn test_embedded_syntaxes() {
+        let config = ConfigRenderer::default();
+        let html = highlight_markdown(config, "```swift\nlet x = 1\n```\n");
+        assert!(html.contains(r#"<pre class="Swift">"#));
+    }
+
+    #[test]
+    fn test_syntaxes_folder() {
+        let folder = std::env::temp_dir().join("techou-syntaxes-test");
+        std::fs::create_dir_all(folder.join("syntaxes")).unwrap();
+        std::fs::write(
+            folder.join("syntaxes/Greeting.sublime-syntax"),
+            "%YAML 1.2\n---\nname: Greeting\nfile_extensions: [greeting]\nscope: source.greeting\ncontexts:\n  main:\n    - match: '\\bhello\\b'\n      scope: keyword.greeting\n",
+        )
+        .unwrap();
+        let mut config = ConfigRenderer::default();
+        config.project_root = folder;
+        let html = highlight_markdown(config, "```greeting\nhello world\n```\n");
+        assert!(html.contains(r#"<pre class="Greeting">"#));
+        assert!(html.contains(r#"<span class="keyword greeting">hello</span>"#));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_highlighters() {
+        let mut config = ConfigRenderer::default();
+        config
+            .external_highlighters
+            .insert("upper".to_string(), "tr a-z A-Z".to_string());
+        config
+            .external_highlighters
+            .insert("broken".to_string(), "false".to_string());
+        let html = highlight_markdown(config.clone(), "```upper\nhello\n```\n");
+        assert!(html.contains("<code>HELLO</code>"));
+        // Failing highlighters fall back to plain text
+        let html = highlight_markdown(config, "```broken\n<b>\n```\n");
+        assert!(html.contains(r#"<pre class="Plain Text"><code>&lt;b&gt;</code>"#));
     }
 }
@@ -0,0 +1,78 @@
+use super::*;
+
+use pulldown_cmark::CowStr;
+
+use crate::images::{DocumentImages, ProcessedImage};
+
+struct CurrentImage {
+    processed: ProcessedImage,
+    src: String,
+    title: String,
+    alt: String,
+}
+
+/// Replaces local images with responsive images (`srcset`, `width` and `height`)
+pub struct ImagesEventHandler<'a> {
+    images: DocumentImages<'a>,
+    current: Option<CurrentImage>,
+}
+
+impl<'a> ImagesEventHandler<'a> {
+    pub fn new(images: DocumentImages<'a>) -> ImagesEventHandler<'a> {
+        ImagesEventHandler {
+            images,
+            current: None,
+        }
+    }
+}
+
+impl<'a> EventHandler for ImagesEventHandler<'a> {
+    fn handle(&mut self, event: &Event, result: &mut ParseResult, events: &mut Vec<Event>) -> bool {
+        match event {
+            Event::Start(Tag::Image(_, url, title)) => {
+                let processor = self.images.processor;
+                let source = match processor.source_path(url, self.images.folder) {
+                    Some(source) => source,
+                    None => return true,
+                };
+                match processor.process(&source) {
+                    Ok(processed) => {
+                        result.images.push(source);
+                        self.current = Some(CurrentImage {
+                            processed,
+                            src: url.to_string(),
+                            title: title.to_string(),
+                            alt: String::new(),
+                        });
+                        false
+                    }
+                    Err(e) => {
+                        println!("{}", &e);
+                        true
+                    }
+                }
+            }
+            Event::End(Tag::Image(_, _, _)) if self.current.is_some() => {
+                if let Some(current) = self.current.take() {
+                    let html = self.images.processor.html(
+                        &current.processed,
+                        &current.src,
+                        &current.alt,
+                        &current.title,
+                    );
+                    events.push(Event::Html(CowStr::Boxed(html.into_boxed_str())));
+                }
+                false
+            }
+            // The alt text of the image
+            Event::Text(text) | Event::Code(text) if self.current.is_some() => {
+                if let Some(current) = self.current.as_mut() {
+                    current.alt.push_str(text);
+                }
+                false
+            }
+            _ if self.current.is_some() => false,
+            _ => true,
+        }
+    }
+}
@@ -14,7 +14,9 @@ enum LinkType<'a> {
     Link,
     ShortLink(Cow<'a, str>),
     RelLink(Cow<'a, str>),
-    Id(Cow<'a, str>, Cow<'a, str>),
+    /// `id::heading` or `id::file.md::heading`, a link to the anchor of a heading
+    /// on the current page or in a file relative to the current root
+    Id(Option<Cow<'a, str>>, Cow<'a, str>),
 }
 
 impl<'a> LinksEventHandler<'a> {
@@ -28,6 +30,12 @@ impl<'a> LinksEventHandler<'a> {
         }
     }
 
+    /// The absolute link for a `file.md` within the current root
+    fn relative_path(&self, file: &str) -> String {
+        let base = self.base_folder.unwrap_or_default();
+        format!("/{}/{}", &base, &file.replace(".md", ".html"))
+    }
+
     fn detect_link_type(link: &'a CowStr) -> LinkType<'a> {
         let items: Vec<&str> = link.split("::").collect();
         // a normal link
@@ -40,8 +48,9 @@ impl<'a> LinksEventHandler<'a> {
         match items[0] {
             "lnk" => return LinkType::ShortLink(Cow::Borrowed(items[1])),
             "rel" => return LinkType::RelLink(Cow::Borrowed(items[1])),
+            "id" if items.len() == 2 => return LinkType::Id(None, Cow::Borrowed(items[1])),
             "id" if items.len() == 3 => {
-                return LinkType::Id(Cow::Borrowed(items[1]), Cow::Borrowed(items[2]))
+                return LinkType::Id(Some(Cow::Borrowed(items[1])), Cow::Borrowed(items[2]))
             }
             _ => return LinkType::Link,
         }
@@ -60,8 +69,7 @@ impl<'a> EventHandler for LinksEventHandler<'a> {
             Event::Start(Tag::Link(a, b, c)) => match LinksEventHandler::detect_link_type(b) {
                 LinkType::Link => true,
                 LinkType::RelLink(tag) => {
-                    let base = self.base_folder.unwrap_or_default();
-                    let full_path = format!("/{}/{}", &base, &tag.replace(".md", ".html"));
+                    let full_path = self.relative_path(&tag);
                     events.push(Event::Start(Tag::Link(
                         a.clone(),
                         CowStr::Boxed(full_path.into_boxed_str()),
@@ -69,11 +77,14 @@ impl<'a> EventHandler for LinksEventHandler<'a> {
                     )));
                     return false;
                 }
-                LinkType::Id(tag, i) => {
-                    let hashed = utils::hash_string(&tag, 8);
+                LinkType::Id(file, heading) => {
+                    let anchor = utils::anchor_slug(&heading);
+                    let page = file
+                        .map(|file| self.relative_path(&file))
+                        .unwrap_or_default();
                     events.push(Event::Start(Tag::Link(
                         a.clone(),
-                        CowStr::Boxed(format!("#{hashed}-{i}").into_boxed_str()),
+                        CowStr::Boxed(format!("{page}#{anchor}").into_boxed_str()),
                         CowStr::Boxed(c.clone().into_string().into_boxed_str()),
                     )));
                     return false;
@@ -88,7 +99,6 @@ impl<'a> EventHandler for LinksEventHandler<'a> {
                         return false;
                     } else {
                         println!("Could not find short-link for tag {}", &tag);
-                        dbg!(&self.link_database);
                         return true;
                     }
                 }
@@ -97,3 +107,20 @@ impl<'a> EventHandler for LinksEventHandler<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigRenderer;
+    use crate::markdown::markdown_to_html;
+
+    #[test]
+    fn test_id_links() {
+        let config = ConfigRenderer::default();
+        let contents = "[a](id::Intro) [b](id::chapter2.md::Intro)";
+        let result = markdown_to_html(contents, &None, Some("book"), &config, None, None, None);
+        assert!(result.content.contains(r##"<a href="#intro">a</a>"##));
+        assert!(result
+            .content
+            .contains(r##"<a href="/book/chapter2.html#intro">b</a>"##));
+    }
+}
@@ -0,0 +1,289 @@
+use super::*;
+
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use pulldown_cmark::{CodeBlockKind, CowStr, LinkType, Parser};
+
+use crate::utils::escape_xml;
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The prefixes of the code spans that `preprocess_math` turns the math into
+const INLINE_PREFIX: &str = "math::inline::";
+const DISPLAY_PREFIX: &str = "math::display::";
+
+/// Turn `$inline$` and `$$display$$` math into code spans, so that markdown
+/// doesn't touch the LaTeX (e.g. `\{` or `a_1*b_2*`). `MathEventHandler`
+/// renders them. Dollars in code, raw html, urls and escaped dollars (`\$`) are
+/// kept as they are.
+pub fn preprocess_math<'a>(markdown: &'a str) -> Cow<'a, str> {
+    if !markdown.contains('$') {
+        return Cow::Borrowed(markdown);
+    }
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for range in verbatim_ranges(markdown) {
+        // Ranges within a previous range
+        if range.start < position {
+            continue;
+        }
+        replace_math(&markdown[position..range.start], &mut output);
+        output.push_str(&markdown[range.clone()]);
+        position = range.end;
+    }
+    replace_math(&markdown[position..], &mut output);
+    Cow::Owned(output)
+}
+
+/// The fenced and indented code blocks, html blocks, inline html and autolinks
+/// in `markdown`
+fn verbatim_ranges(markdown: &str) -> Vec<Range<usize>> {
+    Parser::new(markdown)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Html(_) => Some(range),
+            Event::Start(Tag::Link(LinkType::Autolink | LinkType::Email, _, _)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+fn replace_math(text: &str, output: &mut String) {
+    let mut position = 0;
+    while let Some(offset) = text[position..].find(['\\', '`', '$']) {
+        let start = position + offset;
+        output.push_str(&text[position..start]);
+        let rest = &text[start..];
+        position = if rest.starts_with('\\') {
+            // Keep the escape and the escaped character
+            let length = rest[1..].chars().next().map(char::len_utf8).unwrap_or(0) + 1;
+            output.push_str(&rest[..length]);
+            start + length
+        } else if rest.starts_with('`') {
+            let code = code_span_length(rest);
+            output.push_str(&rest[..code]);
+            start + code
+        } else if let Some(length) = url_length(text, start) {
+            output.push_str(&rest[..length]);
+            start + length
+        } else if let Some((latex, length)) = display_math(rest) {
+            output.push_str(&code_span(DISPLAY_PREFIX, latex));
+            start + length
+        } else if let Some((latex, length)) = inline_math(rest) {
+            output.push_str(&code_span(INLINE_PREFIX, latex));
+            start + length
+        } else {
+            // A `$` that doesn't start math, or `$$`
+            let length = rest.len() - rest.trim_start_matches('$').len();
+            output.push_str(&rest[..length]);
+            start + length
+        };
+    }
+    output.push_str(&text[position..]);
+}
+
+/// The length of the rest of the url that contains `start`, e.g. for the `$` in
+/// `https://example.com/?price=$5$`
+fn url_length(text: &str, start: usize) -> Option<usize> {
+    let is_boundary = |c: char| c.is_ascii_whitespace() || c == '(' || c == '<';
+    let word_start = text[..start].rfind(is_boundary).map(|i| i + 1).unwrap_or(0);
+    if !text[word_start..start].contains("://") {
+        return None;
+    }
+    let rest = &text[start..];
+    Some(
+        rest.find(|c: char| is_boundary(c) || c == ')' || c == '>')
+            .unwrap_or(rest.len()),
+    )
+}
+
+/// The length of the code span at the start of `text`, or of the backticks
+/// if they're not closed
+fn code_span_length(text: &str) -> usize {
+    let fence = text.len() - text.trim_start_matches('`').len();
+    let mut position = fence;
+    while let Some(offset) = text[position..].find('`') {
+        let start = position + offset;
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == fence {
+            return start + run;
+        }
+        position = start + run;
+    }
+    fence
+}
+
+/// `$$latex$$` within a paragraph
+fn display_math(text: &str) -> Option<(&str, usize)> {
+    let inner = text.strip_prefix("$$")?;
+    let end = inner.find("$$")?;
+    let latex = &inner[..end];
+    if latex.trim().is_empty() || latex.contains("\n\n") {
+        return None;
+    }
+    Some((latex, end + 4))
+}
+
+/// `$latex$`: The opening `$` is not followed by whitespace, the closing one
+/// not preceded by whitespace and not followed by a digit, so that `$5 and $10`
+/// is not math
+fn inline_math(text: &str) -> Option<(&str, usize)> {
+    let inner = text.strip_prefix('$')?;
+    if inner.starts_with(char::is_whitespace) || inner.starts_with('$') {
+        return None;
+    }
+    let mut position = 0;
+    while let Some(offset) = inner[position..].find(['$', '\\', '\n']) {
+        let end = position + offset;
+        let rest = &inner[end..];
+        if rest.starts_with('\\') {
+            position = end + 1 + rest[1..].chars().next().map(char::len_utf8).unwrap_or(0);
+            continue;
+        }
+        if rest.starts_with('\n') {
+            // Math doesn't continue after a blank line
+            if inner[end + 1..]
+                .trim_start_matches([' ', '\t'])
+                .starts_with('\n')
+            {
+                return None;
+            }
+            position = end + 1;
+            continue;
+        }
+        let latex = &inner[..end];
+        let closes = !latex.is_empty()
+            && !latex.ends_with(char::is_whitespace)
+            && !rest[1..].starts_with(|c: char| c.is_ascii_digit());
+        if closes {
+            return Some((latex, end + 2));
+        }
+        position = end + 1;
+    }
+    None
+}
+
+/// A code span with `prefix` and `contents` that is fenced with enough backticks
+fn code_span(prefix: &str, contents: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in contents.chars() {
+        current = if c == '`' { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+    let fence = "`".repeat(longest + 1);
+    format!("{} {}{} {}", fence, prefix, contents, fence)
+}
+
+/// The LaTeX in a code span from `preprocess_math` and whether it is display math
+pub fn math_source(code: &str) -> Option<(&str, bool)> {
+    if let Some(latex) = code.strip_prefix(INLINE_PREFIX) {
+        return Some((latex, false));
+    }
+    code.strip_prefix(DISPLAY_PREFIX).map(|latex| (latex, true))
+}
+
+/// Render LaTeX to MathML. Formulas that can't be rendered are reported and
+/// shown as code
+fn render_math(latex: &str, display: bool) -> String {
+    let style = match display {
+        true => DisplayStyle::Block,
+        false => DisplayStyle::Inline,
+    };
+    match latex_to_mathml(latex.trim(), style) {
+        Ok(mathml) => mathml,
+        Err(e) => {
+            println!("Could not render math `{}`: {}", latex.trim(), e);
+            format!("<code class=\"math-error\">{}</code>", escape_xml(latex))
+        }
+    }
+}
+
+/// Renders `$inline$`, `$$display$$` math and ```` ```math ```` blocks to MathML.
+/// The markdown has to be prepared with `preprocess_math`.
+#[derive(Default)]
+pub struct MathEventHandler {
+    current_block: Option<String>,
+}
+
+impl EventHandler for MathEventHandler {
+    fn handle(
+        &mut self,
+        event: &Event,
+        _result: &mut ParseResult,
+        events: &mut Vec<Event>,
+    ) -> bool {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split([',', ' ']).next() == Some("math") =>
+            {
+                self.current_block = Some(String::new());
+                false
+            }
+            Event::Text(text) if self.current_block.is_some() => {
+                if let Some(block) = self.current_block.as_mut() {
+                    block.push_str(text);
+                }
+                false
+            }
+            Event::End(Tag::CodeBlock(_)) if self.current_block.is_some() => {
+                if let Some(block) = self.current_block.take() {
+                    events.push(Event::Html(CowStr::Boxed(
+                        render_math(&block, true).into_boxed_str(),
+                    )));
+                }
+                false
+            }
+            Event::Code(code) => match math_source(code) {
+                Some((latex, display)) => {
+                    events.push(Event::Html(CowStr::Boxed(
+                        render_math(latex, display).into_boxed_str(),
+                    )));
+                    false
+                }
+                None => true,
+            },
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigRenderer;
+    use crate::markdown::markdown_to_html;
+
+    #[test]
+    fn test_preprocess_math() {
+        let markdown = "Let $a_1*b_2*$ and $$\\{x\\}$$, but `$code$`, \\$5 and $5 or $10.\n\n```\n$block$\n```\n";
+        assert_eq!(
+            preprocess_math(markdown),
+            "Let ` math::inline::a_1*b_2* ` and ` math::display::\\{x\\} `, but `$code$`, \\$5 and $5 or $10.\n\n```\n$block$\n```\n"
+        );
+        assert_eq!(code_span(INLINE_PREFIX, "`a`"), "`` math::inline::`a` ``");
+        assert_eq!(preprocess_math("$a\n\nb$"), "$a\n\nb$");
+    }
+
+    #[test]
+    fn test_preprocess_math_verbatim() {
+        let markdown = "Text\n\n    indented $code$\n\n<div title=\"$html$\">\n</div>\n\n<https://example.com/$a$> and https://example.com/?x=$b$ but $c$\n";
+        assert_eq!(
+            preprocess_math(markdown),
+            "Text\n\n    indented $code$\n\n<div title=\"$html$\">\n</div>\n\n<https://example.com/$a$> and https://example.com/?x=$b$ but ` math::inline::c `\n"
+        );
+    }
+
+    #[test]
+    fn test_math() {
+        let mut config = ConfigRenderer::default();
+        config.markdown_math = true;
+        let markdown = "Inline $x^2$ math\n\n```math\n\\frac{1}{2}\n```\n";
+        let result = markdown_to_html(markdown, &None, None, &config, None, None, None);
+        assert!(result.content.contains("<p>Inline <math"));
+        assert!(result.content.contains("<msup>"));
+        assert!(result.content.contains("display=\"block\""));
+        assert!(result.content.contains("<mfrac>"));
+        assert!(!result.content.contains("<pre"));
+    }
+}
@@ -1,14 +1,24 @@
+use crate::document::Section;
 use pulldown_cmark::{Event, Tag};
 
+use std::path::PathBuf;
+
 pub trait EventHandler {
     fn handle(&mut self, event: &Event, result: &mut ParseResult, events: &mut Vec<Event>) -> bool;
 }
 
 pub struct ParseResult {
     pub content: String,
-    pub sections: Vec<(u32, String)>,
+    /// The sections while parsing, nested by their level afterwards
+    pub sections: Vec<Section>,
+    /// The local images that were processed
+    pub images: Vec<PathBuf>,
 }
 
 pub mod highlight;
 pub mod section;
 pub mod links;
+pub mod images;
+pub mod math;
+pub mod diagrams;
+pub mod admonitions;
@@ -1,26 +1,30 @@
 use super::*;
 
+use super::math::math_source;
+use crate::front_matter::SectionLimit;
+use crate::utils::HeadingAnchors;
 use pulldown_cmark::CowStr;
 
 pub struct SectionEventHandler<'a> {
-    base_identifier: &'a str,
     header_section_html: &'a str,
     next_text_is_section: bool,
     current_header: String,
-    limit_parsed_sections: Option<usize>,
+    current_id: Option<String>,
+    anchors: HeadingAnchors,
+    limit_parsed_sections: Option<SectionLimit>,
 }
 
 impl<'a> SectionEventHandler<'a> {
     pub fn new(
-        base_identifier: &'a str,
         header_section_html: &'a str,
-        limit_parsed_sections: Option<usize>,
+        limit_parsed_sections: Option<SectionLimit>,
     ) -> SectionEventHandler<'a> {
         SectionEventHandler {
-            base_identifier,
             header_section_html,
             next_text_is_section: false,
             current_header: String::new(),
+            current_id: None,
+            anchors: HeadingAnchors::default(),
             limit_parsed_sections,
         }
     }
@@ -29,31 +33,103 @@ impl<'a> SectionEventHandler<'a> {
 impl<'a> EventHandler for SectionEventHandler<'a> {
     fn handle(&mut self, event: &Event, result: &mut ParseResult, events: &mut Vec<Event>) -> bool {
         match event {
-            Event::Start(Tag::Heading(_, _, _)) => {
+            Event::Start(Tag::Heading(_, id, _)) => {
                 self.next_text_is_section = true;
+                self.current_id = id.map(|id| id.to_string());
             }
             Event::Text(ref text) if self.next_text_is_section => {
                 self.current_header.push_str(&text);
             }
-            Event::End(Tag::Heading(_, _, _)) => {
+            Event::Code(ref code) if self.next_text_is_section => {
+                // Math in headings is titled with its LaTeX
+                let text = math_source(code).map(|(latex, _)| latex).unwrap_or(code);
+                self.current_header.push_str(text);
+            }
+            Event::End(Tag::Heading(level, _, _)) => {
                 self.next_text_is_section = false;
-                let header_number = (result.sections.len() as u32) + 1;
-                if let Some(n) = self.limit_parsed_sections {
-                    if header_number > n as u32 {
-                        return true;
-                    }
+                let text = std::mem::take(&mut self.current_header);
+                let explicit_id = self.current_id.take();
+                let anchor = self.anchors.anchor(&text, explicit_id.as_deref());
+                // Every heading gets an anchor, but not all of them are sections
+                let level = *level as u32;
+                let is_section = self
+                    .limit_parsed_sections
+                    .map(|limit| limit.includes(level, result.sections.len()))
+                    .unwrap_or(true);
+                if is_section {
+                    result.sections.push(Section {
+                        level,
+                        anchor: anchor.clone(),
+                        title: text,
+                        children: Vec::new(),
+                    });
+                }
+                // Headings with an explicit id already have it as their `id` attribute
+                if explicit_id.is_none() {
+                    // we insert a small identifier so that the header can be linked to
+                    // `{identifier}-{number}` is the placeholder of older configurations
+                    let string = self
+                        .header_section_html
+                        .replace("{identifier}-{number}", &anchor)
+                        .replace("{anchor}", &anchor);
+                    events.push(Event::Html(CowStr::Boxed(string.into_boxed_str())));
                 }
-                let text = std::mem::replace(&mut self.current_header, String::new());
-                result.sections.push((header_number, text));
-                // we insert a small identifier so that the header can be linked to
-                let string = self
-                    .header_section_html
-                    .replace("{identifier}", self.base_identifier)
-                    .replace("{number}", &header_number.to_string());
-                events.push(Event::Html(CowStr::Boxed(string.into_boxed_str())));
             }
             _ => (),
         }
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigRenderer;
+    use crate::document::Section;
+    use crate::front_matter::SectionLimit;
+    use crate::markdown::markdown_to_html;
+
+    #[test]
+    fn test_heading_anchors() {
+        let config = ConfigRenderer::default();
+        let contents = r#"
+# Hello, World!
+## The `map` function
+## Hello World
+## Custom {#my-id}
+## Hello World"#;
+        let limit = Some(SectionLimit::Count(4));
+        let result = markdown_to_html(contents, &None, None, &config, limit, None, None);
+        let sections = Section::flattened(&result.sections);
+        let anchors: Vec<&str> = sections.iter().map(|s| s.anchor.as_str()).collect();
+        assert_eq!(
+            anchors,
+            vec!["hello-world", "the-map-function", "hello-world-1", "my-id"]
+        );
+        assert_eq!(sections[1].title, "The map function");
+        assert!(result.content.contains(r#"<h2 id="my-id">Custom</h2>"#));
+        assert!(result
+            .content
+            .contains(r#"<span id="hello-world-2"></span>"#));
+    }
+
+    #[test]
+    fn test_nested_sections() {
+        let config = ConfigRenderer::default();
+        let contents = "# One\n## Two\n### Three\n#### Four\n## Five\n# Six";
+        let limit = Some(SectionLimit::Depth {
+            depth: 3,
+            count: None,
+        });
+        let result = markdown_to_html(contents, &None, None, &config, limit, None, None);
+        let titles = |sections: &[Section]| -> Vec<String> {
+            sections.iter().map(|s| s.title.clone()).collect()
+        };
+        assert_eq!(titles(&result.sections), vec!["One", "Six"]);
+        assert_eq!(titles(&result.sections[0].children), vec!["Two", "Five"]);
+        assert_eq!(
+            titles(&result.sections[0].children[0].children),
+            vec!["Three"]
+        );
+        assert_eq!(result.sections[0].children[0].children[0].level, 3);
+    }
+}
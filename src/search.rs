@@ -11,6 +11,9 @@ use crate::book::Book;
 use crate::config::Config;
 use crate::document::Document;
 use crate::error::*;
+use crate::front_matter::MarkdownOverrides;
+use crate::parse_event_handlers::admonitions::{preprocess_admonitions, strip_marker};
+use crate::parse_event_handlers::math::{math_source, preprocess_math};
 use crate::utils;
 
 // This was lifted & adapted from mdbook / searcher.rs
@@ -54,8 +57,8 @@ impl<'a> Searcher<'a> {
 
     /// Index one document
     pub fn index_document(&mut self, document: &Document) -> Result<()> {
-        // Don't index documents that opt out of indexing
-        if !document.info.indexed {
+        // Don't index documents that opt out of indexing or are drafts
+        if !document.info.indexed || document.info.draft {
             return Ok(());
         }
         self.render_item(
@@ -63,6 +66,7 @@ impl<'a> Searcher<'a> {
             &document.slug,
             &document.info.description_html,
             &document.raw_content,
+            &document.info.markdown,
         )
     }
 
@@ -79,11 +83,12 @@ impl<'a> Searcher<'a> {
                 &book.slug,
                 &book.info.description_html,
                 "",
+                &book.info.markdown,
             )?;
         }
         for chapter in &book.chapters {
-            // Don't index documents that opt out of indexing
-            if !chapter.document.info.indexed {
+            // Don't index documents that opt out of indexing or are drafts
+            if !chapter.document.info.indexed || chapter.document.info.draft {
                 continue;
             }
             self.render_item(
@@ -91,6 +96,7 @@ impl<'a> Searcher<'a> {
                 &chapter.document.slug,
                 &chapter.document.info.description_html,
                 &chapter.document.raw_content,
+                &chapter.document.info.markdown,
             )?;
         }
         Ok(())
@@ -113,20 +119,31 @@ impl<'a> Searcher<'a> {
         slug: &str,
         description_html: &str,
         contents: &str,
+        markdown: &MarkdownOverrides,
     ) -> Result<()> {
         // As said below, we have to have one parse method and not do it 5 times.
         // this is terrible for performance
-        let first_index = match contents.find("---\n") {
-            Some(o) => o,
-            None => return Ok(()),
+        let article = match crate::front_matter::detect_front_matter(contents, slug, self.config) {
+            Ok((_, _, article)) => article,
+            Err(_) => return Ok(()),
         };
 
-        let mut opts = Options::empty();
-        opts.insert(Options::ENABLE_TABLES);
-        opts.insert(Options::ENABLE_FOOTNOTES);
+        // The same extensions as the rendered document, so that the text matches
+        let render = markdown.apply(&self.config.render);
+        let opts = render.markdown_options();
         // Cut out the front matter. This should happen in one go that parses everything out of the markdown
         // that we need.
-        let p = Parser::new_ext(&contents[(first_index + 4)..], opts);
+        let article = crate::shortcodes::strip_shortcodes(article);
+        let article = crate::wiki_links::preprocess_wiki_links(&article);
+        let article = match render.markdown_admonitions {
+            true => Cow::Owned(preprocess_admonitions(&article).into_owned()),
+            false => article,
+        };
+        let article = match render.markdown_math {
+            true => Cow::Owned(preprocess_math(&article).into_owned()),
+            false => article,
+        };
+        let p = Parser::new_ext(&article, opts);
 
         // FIXME: Instead of parsing each document 10 times we should do it once in a seperate place that does everything
 
@@ -137,12 +154,57 @@ impl<'a> Searcher<'a> {
         let mut body = String::new();
         let mut breadcrumbs = vec![title.to_string()];
         let mut footnote_numbers = HashMap::new();
-        let mut heading_counter = 1;
+        // The anchors are generated like the sections of the document, for all headings
+        let mut anchors = utils::HeadingAnchors::default();
+        let mut anchor_text: Option<String> = None;
+        let mut explicit_id: Option<String> = None;
+        let mut anchor = None;
+        // The first line of a blockquote, until it is known whether it is an admonition
+        let mut admonition_line: Option<String> = None;
 
         // add the description, too
         self.add_doc(&slug, &None, &[&title, &clean_html(&description_html)]);
 
         for event in p {
+            // The `[!NOTE]` marker of admonitions is not indexed, only their title and text
+            if let Some(line) = admonition_line.as_mut() {
+                match &event {
+                    Event::Start(Tag::Paragraph) if line.is_empty() => (),
+                    Event::Text(text) => {
+                        line.push_str(text);
+                        continue;
+                    }
+                    _ => {
+                        body.push_str(strip_marker(line));
+                        admonition_line = None;
+                    }
+                }
+            }
+            if render.markdown_admonitions && matches!(event, Event::Start(Tag::BlockQuote)) {
+                admonition_line = Some(String::new());
+            }
+            match &event {
+                Event::Start(Tag::Heading(_, id, _)) => {
+                    anchor_text = Some(String::new());
+                    explicit_id = id.map(|id| id.to_string());
+                }
+                Event::Text(text) => {
+                    if let Some(anchor_text) = anchor_text.as_mut() {
+                        anchor_text.push_str(text);
+                    }
+                }
+                Event::Code(code) => {
+                    if let Some(anchor_text) = anchor_text.as_mut() {
+                        anchor_text
+                            .push_str(math_source(code).map(|(latex, _)| latex).unwrap_or(code));
+                    }
+                }
+                Event::End(Tag::Heading(_, _, _)) => {
+                    let text = anchor_text.take().unwrap_or_default();
+                    anchor = Some(anchors.anchor(&text, explicit_id.take().as_deref()));
+                }
+                _ => (),
+            }
             match event {
                 Event::Start(Tag::Heading(i, _, _)) if tusize(&i) <= max_section_depth => {
                     if !heading.is_empty() {
@@ -159,14 +221,11 @@ impl<'a> Searcher<'a> {
                         breadcrumbs.pop();
                     }
 
-                    heading_counter += 1;
-
                     in_header = true;
                 }
                 Event::End(Tag::Heading(i, _, _)) if tusize(&i) <= max_section_depth => {
                     in_header = false;
-                    //section_id = Some(utils::id_from_content(&heading));
-                    section_id = Some(format!("head-{}", &heading_counter));
+                    section_id = anchor.take();
 
                     breadcrumbs.push(heading.clone());
                 }
@@ -194,7 +253,9 @@ impl<'a> Searcher<'a> {
                     body.push_str(&clean_html(&html));
                 }
                 Event::Code(html) => {
-                    body.push_str(&clean_html(&html));
+                    // The LaTeX of math
+                    let code = math_source(&html).map(|(latex, _)| latex).unwrap_or(&html);
+                    body.push_str(&clean_html(code));
                 }
                 Event::Rule => {
                     body.push_str("<hr/>");
@@ -309,3 +370,45 @@ fn tusize(input: &pulldown_cmark::HeadingLevel) -> i32 {
         pulldown_cmark::HeadingLevel::H6 => 5,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_section_anchors() {
+        use crate::config::Config;
+        use crate::document::Section;
+        use crate::markdown::markdown_to_html;
+        use crate::search::Searcher;
+        let mut config = Config::default();
+        // Split at `#` and `##`, the `###` stays in the first section
+        config.search.heading_split_level = 1;
+        let markdown =
+            "# Hello, World!\nText\n### Hello World\n## Hello World\n## Custom {#custom}\n";
+        let contents = format!(
+            "[frontMatter]\ntitle = \"Title\"\ncreated = \"2009-12-30\"\n---\n{}",
+            markdown
+        );
+        let mut searcher = Searcher::new(&config);
+        searcher
+            .render_item("Title", "/a.html", "", &contents, &Default::default())
+            .unwrap();
+        assert_eq!(
+            searcher.doc_urls,
+            vec![
+                "/a.html",
+                "/a.html#hello-world",
+                "/a.html#hello-world-2",
+                "/a.html#custom"
+            ]
+        );
+        let result = markdown_to_html(markdown, &None, None, &config.render, None, None, None);
+        let anchors: Vec<&str> = Section::flattened(&result.sections)
+            .iter()
+            .map(|s| s.anchor.as_str())
+            .collect();
+        assert_eq!(
+            anchors,
+            vec!["hello-world", "hello-world-1", "hello-world-2", "custom"]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use lazy_static::*;
+use regex::Regex;
+use serde_derive::Serialize;
+use tera::Tera;
+
+use crate::config::Config;
+use crate::error::{Result, ResultContext, TechouError};
+use crate::front_matter::FrontMatter;
+use crate::utils::fenced_code_blocks;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+
+const OPEN: &str = "{{<";
+const CLOSE: &str = ">}}";
+
+lazy_static! {
+    static ref SHORTCODE_TAG: Regex = Regex::new(r"\{\{<\s*/?\s*[\w-]+[^>]*>\}\}").unwrap();
+    static ref ARGUMENT: Regex =
+        Regex::new(r#"(?:([\w-]+)\s*=\s*)?(?:"((?:[^"\\]|\\.)*)"|'([^']*)'|([^\s"']+))"#).unwrap();
+}
+
+/// The document a shortcode is rendered in. Available as `document` in the
+/// shortcode templates
+#[derive(Serialize, Debug)]
+pub struct ShortcodeDocument<'a> {
+    pub identifier: &'a str,
+    pub filename: &'a str,
+    pub slug: &'a str,
+    pub info: &'a FrontMatter,
+}
+
+#[derive(Serialize, Debug)]
+struct ShortcodeContext<'a> {
+    name: &'a str,
+    args: &'a BTreeMap<String, String>,
+    inner: Option<&'a str>,
+    inner_html: Option<String>,
+    document: &'a ShortcodeDocument<'a>,
+    config: &'a Config,
+}
+
+/// Shortcodes in markdown such as `{{< youtube id="abc" >}}` or the block form
+/// `{{< note >}}Content{{< /note >}}`. Each shortcode is rendered with the
+/// template `{name}.html` from the shortcodes folder. `{{</* name */>}}` writes
+/// the shortcode itself without rendering it.
+pub struct Shortcodes {
+    tera: Tera,
+}
+
+impl Shortcodes {
+    /// Load the shortcode templates from `directory`. If the directory doesn't
+    /// exist, there're no shortcodes.
+    pub fn new<A: AsRef<Path>>(directory: A) -> Result<Shortcodes> {
+        let directory = directory.as_ref();
+        if !directory.is_dir() {
+            return Ok(Shortcodes::default());
+        }
+        let folder_path = directory
+            .to_str()
+            .expect("Could not find shortcodes folder")
+            .replace("./", "");
+        let mut tera = Tera::new(&format!("{}/*.html", folder_path)).ctx(&folder_path)?;
+        tera.autoescape_on(vec![]);
+        Ok(Shortcodes { tera })
+    }
+
+    /// Render all shortcodes in `markdown`. Shortcodes in fenced code blocks are ignored.
+    pub fn render<'a>(
+        &self,
+        markdown: &'a str,
+        document: &ShortcodeDocument,
+        config: &Config,
+    ) -> Result<Cow<'a, str>> {
+        if !markdown.contains(OPEN) {
+            return Ok(Cow::Borrowed(markdown));
+        }
+        let code_blocks = fenced_code_blocks(markdown);
+        let mut output = String::with_capacity(markdown.len());
+        let mut position = 0;
+        while let Some(found) = markdown[position..].find(OPEN) {
+            let start = position + found;
+            if let Some(block) = code_blocks.iter().find(|block| block.contains(&start)) {
+                output.push_str(&markdown[position..block.end]);
+                position = block.end;
+                continue;
+            }
+            output.push_str(&markdown[position..start]);
+            let end = match markdown[start..].find(CLOSE) {
+                Some(end) => start + end,
+                None => {
+                    position = start;
+                    break;
+                }
+            };
+            let tag = markdown[start + OPEN.len()..end].trim();
+            position = end + CLOSE.len();
+
+            // Escaped shortcodes are written as they are
+            if tag.starts_with("/*") && tag.ends_with("*/") && tag.len() >= 4 {
+                output.push_str(&format!(
+                    "{} {} {}",
+                    OPEN,
+                    tag[2..tag.len() - 2].trim(),
+                    CLOSE
+                ));
+                continue;
+            }
+
+            let self_closing = tag.ends_with('/');
+            let (name, args) = parse_tag(tag.trim_end_matches('/'));
+            if name.is_empty() || name.starts_with('/') {
+                return Err(TechouError::Other {
+                    issue: format!(
+                        "{}: Invalid shortcode `{}`",
+                        &document.filename,
+                        &markdown[start..position]
+                    ),
+                });
+            }
+
+            let closing = match self_closing {
+                true => None,
+                false => find_closing(&markdown[position..], name),
+            };
+            let inner = match closing {
+                Some(ref range) => {
+                    let inner = &markdown[position..position + range.start];
+                    position += range.end;
+                    Some(self.render(inner, document, config)?)
+                }
+                None => None,
+            };
+            let rendered =
+                self.render_shortcode(name, &args, inner.as_deref(), document, config)?;
+            output.push_str(&rendered);
+        }
+        output.push_str(&markdown[position..]);
+        Ok(Cow::Owned(output))
+    }
+
+    fn render_shortcode(
+        &self,
+        name: &str,
+        args: &BTreeMap<String, String>,
+        inner: Option<&str>,
+        document: &ShortcodeDocument,
+        config: &Config,
+    ) -> Result<String> {
+        let template_name = format!("{}.html", name);
+        if !self.tera.get_template_names().any(|t| t == template_name) {
+            return Err(TechouError::Other {
+                issue: format!("{}: Unknown shortcode `{}`", &document.filename, &name),
+            });
+        }
+        let inner_html = inner.map(|inner| {
+            let mut html = String::new();
+            pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(inner));
+            html
+        });
+        let item = ShortcodeContext {
+            name,
+            args,
+            inner,
+            inner_html,
+            document,
+            config,
+        };
+        let context = tera::Context::from_serialize(&item).ctx(&template_name)?;
+        let rendered = self
+            .tera
+            .render(&template_name, &context)
+            .ctx(&template_name)?;
+        // Empty lines would end a HTML block in the markdown
+        Ok(rendered
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+impl Default for Shortcodes {
+    fn default() -> Self {
+        Shortcodes {
+            tera: Tera::default(),
+        }
+    }
+}
+
+/// Remove the shortcode tags (but not the inner contents) from `markdown`
+pub fn strip_shortcodes<'a>(markdown: &'a str) -> Cow<'a, str> {
+    SHORTCODE_TAG.replace_all(markdown, "")
+}
+
+/// Split `name key="value" other=1 "positional"` into the name and the arguments.
+/// Positional arguments are available by their index (`args["0"]`)
+fn parse_tag(tag: &str) -> (&str, BTreeMap<String, String>) {
+    let tag = tag.trim();
+    let (name, rest) = match tag.find(char::is_whitespace) {
+        Some(index) => (&tag[..index], &tag[index..]),
+        None => (tag, ""),
+    };
+    let mut args = BTreeMap::new();
+    let mut index = 0;
+    for capture in ARGUMENT.captures_iter(rest) {
+        let value = capture
+            .get(2)
+            .map(|m| m.as_str().replace("\\\"", "\""))
+            .or_else(|| {
+                capture
+                    .get(3)
+                    .or_else(|| capture.get(4))
+                    .map(|m| m.as_str().to_string())
+            })
+            .unwrap_or_default();
+        match capture.get(1) {
+            Some(key) => {
+                args.insert(key.as_str().to_string(), value);
+            }
+            None => {
+                args.insert(index.to_string(), value);
+                index += 1;
+            }
+        }
+    }
+    (name, args)
+}
+
+/// The range of the closing `{{< /name >}}` tag in `markdown`
+fn find_closing(markdown: &str, name: &str) -> Option<Range<usize>> {
+    let closing = Regex::new(&format!(r"\{{\{{<\s*/\s*{}\s*>\}}\}}", regex::escape(name))).ok()?;
+    closing.find(markdown).map(|m| m.range())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcodes() -> Shortcodes {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "youtube.html",
+                r#"<iframe src="https://www.youtube.com/embed/{{ args.id }}"></iframe>"#,
+            ),
+            (
+                "note.html",
+                "<div class=\"note\" title=\"{{ document.info.title }}\">\n\n{{ inner_html }}</div>",
+            ),
+        ])
+        .unwrap();
+        tera.autoescape_on(vec![]);
+        Shortcodes { tera }
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        let config = Config::default();
+        let front_matter = "[frontMatter]\ntitle = \"Hello\"\ncreated = \"2009-12-30\"\n---\n";
+        let (info, _) =
+            crate::front_matter::parse_front_matter(front_matter, "test.md", &config).unwrap();
+        let document = ShortcodeDocument {
+            identifier: "",
+            filename: "test.md",
+            slug: "",
+            info: &info,
+        };
+        let contents = r#"# Video
+{{< youtube id="abc" >}}
+
+{{< note >}}*Careful*{{< /note >}}
+
+```
+{{< youtube id="code" >}}
+```
+
+{{</* youtube id="escaped" */>}}"#;
+        let result = shortcodes().render(contents, &document, &config).unwrap();
+        assert!(result.contains("https://www.youtube.com/embed/abc"));
+        assert!(result.contains("<div class=\"note\" title=\"Hello\">\n<p><em>Careful</em></p>"));
+        assert!(result.contains("{{< youtube id=\"code\" >}}"));
+        assert!(result.contains("{{< youtube id=\"escaped\" >}}"));
+        assert!(shortcodes()
+            .render("{{< unknown >}}", &document, &config)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        let (name, args) = parse_tag(r#"figure src="/img/a.png" caption='A "quote"' 42 wide"#);
+        assert_eq!(name, "figure");
+        assert_eq!(args["src"], "/img/a.png");
+        assert_eq!(args["caption"], "A \"quote\"");
+        assert_eq!(args["0"], "42");
+        assert_eq!(args["1"], "wide");
+    }
+}
@@ -28,9 +28,18 @@ impl<'a> SiteMap<'a> {
     }
 
     pub fn add_document(&mut self, document: &Document) {
-        // FIXME: Add support for last-updated
+        // Drafts are only rendered for previews
+        if document.info.draft {
+            return;
+        }
+        use chrono::{DateTime, FixedOffset};
+        let lastmod = document.info.updated_date.unwrap_or(document.info.date);
         let entry = sitemap::structs::UrlEntry::builder()
-            .loc(format!("{}{}", &self.base_url, &document.slug));
+            .loc(format!("{}{}", &self.base_url, &document.slug))
+            .lastmod(DateTime::<FixedOffset>::from_utc(
+                lastmod,
+                FixedOffset::east(0),
+            ));
         self.url_writer.url(entry).unwrap();
     }
 
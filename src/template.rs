@@ -8,9 +8,11 @@ use crate::error::*;
 use crate::io_utils::spit;
 use crate::list::*;
 use crate::filters;
+use crate::images::ImageProcessor;
 use crate::utils::{slugify, hash_string};
 
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::{HashMap, BTreeMap};
 
 struct UrlMaker {
@@ -56,6 +58,58 @@ impl tera::Function for IdentifierHash {
     }
 }
 
+/// `resize_image(path="/img/a.png", width=480)` returns the url of a resized variant
+struct ResizeImage {
+    processor: ImageProcessor,
+}
+impl tera::Function for ResizeImage {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let path = match args.get("path").and_then(|v| v.as_str()) {
+            Some(path) => path,
+            None => return Err(tera::Error::msg("resize_image: Parameter `path` not found")),
+        };
+        let width = match args.get("width").and_then(|v| v.as_u64()) {
+            Some(width) => width as u32,
+            None => return Err(tera::Error::msg("resize_image: Parameter `width` not found")),
+        };
+        let source = self.processor.source_path(path, None)
+            .ok_or_else(|| tera::Error::msg(format!("resize_image: Could not find image {}", path)))?;
+        let variant = self.processor.resize(&source, width)
+            .map_err(|e| tera::Error::msg(format!("resize_image: {}", e)))?;
+        tera::Result::Ok(tera::Value::String(variant.url))
+    }
+}
+
+/// `asset_url(path="css/style.scss")` returns the url of the compiled and fingerprinted asset
+struct AssetUrl {
+    urls: BTreeMap<String, String>,
+}
+impl tera::Function for AssetUrl {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let path = match args.get("path").and_then(|v| v.as_str()) {
+            Some(path) => path.trim_start_matches('/'),
+            None => return Err(tera::Error::msg("asset_url: Parameter `path` not found")),
+        };
+        let url = match self.urls.get(path) {
+            Some(url) => url.clone(),
+            None => format!("/{}", path),
+        };
+        tera::Result::Ok(tera::Value::String(url))
+    }
+}
+
+/// Minify HTML including inline CSS and JS. Whitespace in `pre` blocks is kept
+fn minify(html: &str) -> String {
+    let cfg = minify_html::Cfg {
+        minify_css: true,
+        minify_js: true,
+        keep_comments: false,
+        ..minify_html::Cfg::default()
+    };
+    let minified = minify_html::minify(html.as_bytes(), &cfg);
+    String::from_utf8(minified).unwrap_or_else(|_| html.to_string())
+}
+
 fn recusive_chapter_urls(into_collection: &mut std::collections::BTreeMap<String, String>, chapter: &Chapter, config: &Config) {
     into_collection.insert(chapter.document.identifier.clone(), format!("/{}", chapter.slug));
     for sub_chapter in chapter.sub_chapters.iter() {
@@ -69,6 +123,12 @@ fn recusive_chapter_urls(into_collection: &mut std::collections::BTreeMap<String
 
 pub struct Templates {
     tera: Tera,
+    /// The number of bytes that were saved by minifying the HTML
+    minified_bytes: AtomicUsize,
+    /// The contents of the data folder, available as `data` in all templates
+    data: BTreeMap<String, serde_json::Value>,
+    /// The `url_*` entries of the default language, by function name
+    default_urls: BTreeMap<String, BTreeMap<String, String>>,
 }
 #[derive(Serialize, Debug)]
 struct TemplateContext<'a, T>
@@ -94,10 +154,28 @@ impl Templates {
         tera.autoescape_on(vec![]);
         tera.register_filter("chunks", filters::chunks::Chunk);
         tera.register_filter("split", filters::split::Split);
-        Ok(Templates { tera })
+        Ok(Templates { tera, minified_bytes: AtomicUsize::new(0), data: BTreeMap::new(), default_urls: BTreeMap::new() })
+    }
+
+    pub fn register_asset_functions(&mut self, urls: BTreeMap<String, String>) {
+        self.tera.register_function("asset_url", AssetUrl { urls });
+    }
+
+    pub fn register_image_functions(&mut self, processor: &ImageProcessor) {
+        self.tera.register_function("resize_image", ResizeImage { processor: processor.clone() });
+    }
+
+    pub fn set_data(&mut self, data: BTreeMap<String, serde_json::Value>) {
+        self.data = data;
     }
 
     pub fn register_url_functions(&mut self, context: &DocumentContext, config: &Config) {
+        // The archives of translations are in `/{lang}/folder`
+        let is_default = config.languages.is_default(context.lang);
+        let folder = |name: &str| match is_default {
+            true => name.to_string(),
+            false => format!("{}/{}", context.lang, name),
+        };
         let post_urls: std::collections::BTreeMap<String, String> = context.all_posts.iter()
             .map({ |d|
                 // FIXME: Instead, make sure all slugs always start with / ! (i.e. are absolute)
@@ -107,23 +185,32 @@ impl Templates {
                     (d.identifier.clone(), format!("/{}", &d.slug))
                 }
             }).collect();
-        self.tera.register_function("url_post", UrlMaker::new(post_urls, "url_post"));
+        self.register_urls("url_post", post_urls, is_default);
 
         let page_urls: std::collections::BTreeMap<String, String> = context.pages.iter()
             .map(|d|(d.identifier.clone(), d.slug.clone())).collect();
-        self.tera.register_function("url_page", UrlMaker::new(page_urls, "url_page"));
+        self.register_urls("url_page", page_urls, is_default);
 
         let tag_urls: std::collections::BTreeMap<String, String> = context.by_tag.iter()
-            .map(|t| (t.name.to_string(), format!("/{}/{}.html", config.folders.tags_folder_name, &slugify(&t.name)))).collect();
-        self.tera.register_function("url_tag", UrlMaker::new(tag_urls, "url_tag"));
+            .map(|t| (t.name.to_string(), format!("/{}/{}.html", folder(&config.folders.tags_folder_name), &slugify(&t.name)))).collect();
+        self.register_urls("url_tag", tag_urls, is_default);
 
         let keyword_urls: std::collections::BTreeMap<String, String> = context.by_keyword.iter()
-            .map(|t| (t.name.to_string(), format!("/{}/{}.html", config.folders.keywords_folder_name, &slugify(&t.name)))).collect();
-        self.tera.register_function("url_keyword", UrlMaker::new(keyword_urls, "url_keyword"));
+            .map(|t| (t.name.to_string(), format!("/{}/{}.html", folder(&config.folders.keywords_folder_name), &slugify(&t.name)))).collect();
+        self.register_urls("url_keyword", keyword_urls, is_default);
 
         let category_urls: std::collections::BTreeMap<String, String> = context.by_category.iter()
-            .map(|t| (t.name.to_string(), format!("/{}/{}.html", config.folders.category_folder_name, &slugify(&t.name)))).collect();
-        self.tera.register_function("url_category", UrlMaker::new(category_urls, "url_category"));
+            .map(|t| (t.name.to_string(), format!("/{}/{}.html", folder(&config.folders.category_folder_name), &slugify(&t.name)))).collect();
+        self.register_urls("url_category", category_urls, is_default);
+
+        for taxonomy in config.taxonomies.iter() {
+            let function_name = format!("url_{}", &taxonomy.name);
+            let taxonomy_urls: std::collections::BTreeMap<String, String> = context.by_taxonomy.get(&taxonomy.name)
+                .map(|categories| categories.iter()
+                     .map(|t| (t.name.to_string(), format!("/{}/{}.html", folder(taxonomy.folder_name()), &slugify(&t.name)))).collect())
+                .unwrap_or_default();
+            self.register_urls(&function_name, taxonomy_urls, is_default);
+        }
 
         fn identifier_hash(i: &str) -> String {
             hash_string(i,  8)
@@ -142,6 +229,22 @@ impl Templates {
         self.tera.register_function("url_book", UrlMaker::new(book_urls, "url_book"));
     }
 
+    /// Translations fall back to the urls of the default language, e.g. for the tags of books
+    fn register_urls(&mut self, name: &str, urls: BTreeMap<String, String>, is_default: bool) {
+        let urls = match is_default {
+            true => {
+                self.default_urls.insert(name.to_string(), urls.clone());
+                urls
+            }
+            false => {
+                let mut merged = self.default_urls.get(name).cloned().unwrap_or_default();
+                merged.extend(urls);
+                merged
+            }
+        };
+        self.tera.register_function(name, UrlMaker::new(urls, name));
+    }
+
     pub fn write_post<'a, A: AsRef<Path>>(
         &self,
         context: &DocumentContext<'a>,
@@ -149,12 +252,7 @@ impl Templates {
         path: A,
         config: &Config,
     ) -> Result<()> {
-        let item = TemplateContext {
-            config,
-            context,
-            content: post,
-        };
-        self.write_item(&config.templates.post_template, &item, path, config)
+        self.write_document_with_template(&config.templates.post_template, context, post, path, config)
     }
 
     pub fn write_page<'a, A: AsRef<Path>>(
@@ -163,13 +261,24 @@ impl Templates {
         page: &Document,
         path: A,
         config: &Config,
+    ) -> Result<()> {
+        self.write_document_with_template(&config.templates.page_template, context, page, path, config)
+    }
+
+    pub fn write_document_with_template<'a, A: AsRef<Path>>(
+        &self,
+        template_name: &str,
+        context: &DocumentContext<'a>,
+        document: &Document,
+        path: A,
+        config: &Config,
     ) -> Result<()> {
         let item = TemplateContext {
             config,
             context,
-            content: page,
+            content: document,
         };
-        self.write_item(&config.templates.page_template, &item, &path, config)
+        self.write_item(template_name, &item, &path, config)
     }
 
     pub fn write_book<'a, A: AsRef<Path>>(
@@ -212,8 +321,9 @@ impl Templates {
         self.write_item(&config.templates.chapter_template, &item, &path, config)
     }
 
-    pub fn write_list<'a, A: AsRef<Path>, D: AsRef<Document>>(
+    pub fn write_list_with_template<'a, A: AsRef<Path>, D: AsRef<Document>>(
         &self,
+        template_name: &str,
         context: &DocumentContext<'a>,
         list: &'a List<'a, D>,
         path: A,
@@ -227,25 +337,22 @@ impl Templates {
             context,
             content: list,
         };
-        self.write_item(&config.templates.list_template, &item, path, config)
+        self.write_item(template_name, &item, path, config)
     }
 
-    pub fn write_year<'a, A: AsRef<Path>, D: AsRef<Document>>(
+    pub fn write_overview<'a, A: AsRef<Path>>(
         &self,
         context: &DocumentContext<'a>,
-        list: &'a List<'a, D>,
+        overview: &Overview<'a>,
         path: A,
         config: &Config,
-    ) -> Result<()>
-    where
-        D: Serialize,
-    {
+    ) -> Result<()> {
         let item = TemplateContext {
             config,
             context,
-            content: list,
+            content: overview,
         };
-        self.write_item(&config.templates.year_template, &item, path, config)
+        self.write_item(&config.templates.overview_template, &item, path, config)
     }
 
     fn write_item<'a, A: AsRef<Path>, I: Serialize>(
@@ -253,16 +360,38 @@ impl Templates {
         template_name: &str,
         item: &'a I,
         path: A,
-        _config: &Config,
+        config: &Config,
     ) -> Result<()> {
-        let context = tera::Context::from_serialize(&item)
+        let mut context = tera::Context::from_serialize(&item)
         .map_err(|e| {
             crate::error::TechouError::Templating {
                 source: e,
                 context: "Write item".to_owned()
             }
         })?;
+        context.insert("data", &self.data);
         let rendered = self.tera.render(template_name, &context).ctx(path.as_ref())?;
-        spit(path.as_ref(), &rendered)
+        if !config.render.minify_html {
+            return spit(path.as_ref(), &rendered);
+        }
+        let minified = minify(&rendered);
+        self.minified_bytes.fetch_add(rendered.len().saturating_sub(minified.len()), Ordering::Relaxed);
+        spit(path.as_ref(), &minified)
+    }
+
+    /// The number of bytes that were saved by minifying the HTML
+    pub fn minified_bytes(&self) -> usize {
+        self.minified_bytes.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_minify_keeps_pre() {
+        let html = "<html>\n  <body>\n    <p>  Hello  </p>\n<pre><code>fn a() {\n    b();\n}</code></pre>\n  </body>\n</html>";
+        let minified = super::minify(html);
+        assert!(minified.len() < html.len());
+        assert!(minified.contains("<pre><code>fn a() {\n    b();\n}</code></pre>"));
     }
 }
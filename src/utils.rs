@@ -1,16 +1,31 @@
 use lazy_static::*;
 use regex::Regex;
 use std::borrow::Cow;
+use std::ops::Range;
 
 pub fn hash_string(input: &str, length: usize) -> String {
+    hash_bytes(input.as_bytes(), length)
+}
+
+pub fn hash_bytes(input: &[u8], length: usize) -> String {
     use sha2::Digest;
     use sha2::Sha256;
-    let hash = format!("{:x}", Sha256::digest(input.as_bytes()));
+    let hash = format!("{:x}", Sha256::digest(input));
     // 10 seems to be a good prefix for distinctness
     let (short_hash, _) = hash.split_at(length);
     short_hash.to_string()
 }
 
+/// Escape text for XML (and HTML attributes)
+pub fn escape_xml(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 pub fn slugify(input: &str) -> String {
     input
         .to_lowercase()
@@ -23,6 +38,96 @@ pub fn slugify(input: &str) -> String {
         .join("-")
 }
 
+/// Decode `%20` and friends in urls
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3).unwrap_or_default();
+        if bytes[index] == b'%' && hex.len() == 2 && hex.iter().all(u8::is_ascii_hexdigit) {
+            let hex = std::str::from_utf8(hex).unwrap_or_default();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+            index += 3;
+            continue;
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The anchor for a heading: lowercase, with dashes instead of whitespace and
+/// without punctuation (`Hello, World!` becomes `hello-world`)
+pub fn anchor_slug(input: &str) -> String {
+    let slug = input
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    match slug.is_empty() {
+        true => "section".to_string(),
+        false => slug,
+    }
+}
+
+/// Unique anchors for the headings of one document. Duplicates get a counter
+/// (`intro`, `intro-1`, ...). Used for the sections and the search index so that
+/// both agree.
+#[derive(Default)]
+pub struct HeadingAnchors {
+    used: std::collections::HashSet<String>,
+}
+
+impl HeadingAnchors {
+    /// The anchor for a heading with `title`. An explicit id (`{#custom-id}`) is used as it is
+    pub fn anchor(&mut self, title: &str, explicit_id: Option<&str>) -> String {
+        if let Some(id) = explicit_id {
+            self.used.insert(id.to_string());
+            return id.to_string();
+        }
+        let base = anchor_slug(title);
+        let mut anchor = base.clone();
+        let mut counter = 1;
+        while self.used.contains(&anchor) {
+            anchor = format!("{}-{}", &base, counter);
+            counter += 1;
+        }
+        self.used.insert(anchor.clone());
+        anchor
+    }
+}
+
+/// The byte ranges of the fenced code blocks in `markdown`
+pub fn fenced_code_blocks(markdown: &str) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(usize, &str)> = None;
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let fence = ["```", "~~~"].iter().find(|f| trimmed.starts_with(*f));
+        match (open, fence) {
+            (None, Some(fence)) => open = Some((offset, *fence)),
+            (Some((start, open_fence)), Some(fence)) if open_fence == *fence => {
+                blocks.push(start..offset + line.len());
+                open = None;
+            }
+            _ => (),
+        }
+        offset += line.len();
+    }
+    if let Some((start, _)) = open {
+        blocks.push(start..markdown.len());
+    }
+    blocks
+}
+
 pub fn collapse_whitespace<'a>(text: &'a str) -> Cow<'a, str> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"\s\s+").unwrap();
@@ -71,3 +176,14 @@ impl DebugTimer {
         println!(" Finish {:?}:", next - self.main);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("my%20file.html"), "my file.html");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
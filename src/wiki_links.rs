@@ -0,0 +1,353 @@
+use lazy_static::*;
+use regex::{Captures, Regex};
+
+use crate::document::{Document, DocumentLink, Section};
+use crate::link_checker::{markdown_line, BrokenLink};
+use crate::utils;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The url of a wiki link until it is resolved
+const WIKI_LINK_PREFIX: &str = "wiki::";
+
+lazy_static! {
+    static ref WIKI_LINK: Regex =
+        Regex::new(r"`[^`\n]*`|\[\[([^\[\]|<>\n]+)(?:\|([^\[\]\n]+))?\]\]").unwrap();
+    static ref WIKI_HREF: Regex = Regex::new(r#"href="wiki::([^"]*)""#).unwrap();
+}
+
+/// Turn `[[Target]]` and `[[Target|Label]]` into markdown links to `wiki::Target`.
+/// They're resolved by `resolve_wiki_links` once all documents are loaded.
+/// Wiki links in code are kept as they are.
+pub fn preprocess_wiki_links<'a>(markdown: &'a str) -> Cow<'a, str> {
+    if !markdown.contains("[[") {
+        return Cow::Borrowed(markdown);
+    }
+    let mut code_blocks = utils::fenced_code_blocks(markdown);
+    code_blocks.push(markdown.len()..markdown.len());
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for block in code_blocks {
+        output.push_str(&replace_wiki_links(&markdown[position..block.start]));
+        output.push_str(&markdown[block.clone()]);
+        position = block.end;
+    }
+    Cow::Owned(output)
+}
+
+fn replace_wiki_links<'a>(markdown: &'a str) -> Cow<'a, str> {
+    WIKI_LINK.replace_all(markdown, |captures: &Captures| match captures.get(1) {
+        Some(target) => {
+            let target = target.as_str().trim();
+            let label = captures
+                .get(2)
+                .map(|label| label.as_str().trim())
+                .unwrap_or(target);
+            format!("[{}](<{}{}>)", label, WIKI_LINK_PREFIX, target)
+        }
+        // Inline code
+        None => captures[0].to_string(),
+    })
+}
+
+/// A document that wiki links can point to
+struct WikiTarget {
+    link: DocumentLink,
+    lang: String,
+    sections: Vec<Section>,
+    updated: bool,
+}
+
+/// All documents by their title, slug, filename and identifier
+pub struct WikiLinks {
+    targets: Vec<WikiTarget>,
+    keys: HashMap<String, Vec<usize>>,
+    /// The wiki links that could not be resolved, for the link checker report
+    issues: Vec<BrokenLink>,
+}
+
+impl WikiLinks {
+    pub fn new(documents: &[&mut Document]) -> WikiLinks {
+        let mut keys: HashMap<String, Vec<usize>> = HashMap::new();
+        let targets = documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                for key in document_keys(document) {
+                    let entry = keys.entry(key).or_insert_with(Vec::new);
+                    if !entry.contains(&index) {
+                        entry.push(index);
+                    }
+                }
+                WikiTarget {
+                    link: DocumentLink {
+                        slug: format!("/{}", document.slug.trim_start_matches('/')),
+                        ..document.link()
+                    },
+                    lang: document.lang.clone(),
+                    sections: document.sections.clone(),
+                    updated: document.updated,
+                }
+            })
+            .collect();
+        WikiLinks {
+            targets,
+            keys,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Replace the wiki links in the content of `document` with the slugs of the
+    /// documents they point to. Links that can't be resolved are kept and collected
+    /// in `issues`. Returns the indices of the linked documents.
+    pub fn resolve(&mut self, document: &mut Document) -> Vec<usize> {
+        let mut linked = Vec::new();
+        let mut resolved: HashMap<String, String> = HashMap::new();
+        for captures in WIKI_HREF.captures_iter(&document.content) {
+            let href = &captures[1];
+            if resolved.contains_key(href) {
+                continue;
+            }
+            let target = utils::percent_decode(&href.replace("&#x27;", "'").replace("&amp;", "&"));
+            match self.find(&target, &document.lang) {
+                Ok((index, url)) => {
+                    if !linked.contains(&index) {
+                        linked.push(index);
+                    }
+                    resolved.insert(href.to_string(), url);
+                }
+                Err(reason) => self.issues.push(BrokenLink {
+                    file: PathBuf::from(document.slug.trim_start_matches('/')),
+                    source: Some(document.filename.clone()),
+                    line: markdown_line(document, &format!("[[{}", &target)),
+                    link: format!("[[{}]]", &target),
+                    reason,
+                }),
+            }
+        }
+        if resolved.is_empty() {
+            return linked;
+        }
+        let content =
+            WIKI_HREF.replace_all(&document.content, |captures: &Captures| {
+                match resolved.get(&captures[1]) {
+                    Some(url) => format!("href=\"{}\"", utils::escape_xml(url)),
+                    None => captures[0].to_string(),
+                }
+            });
+        document.content = content.into_owned();
+        // If a linked document changed, its title or slug may have changed, too
+        if linked.iter().any(|index| self.targets[*index].updated) {
+            document.updated = true;
+        }
+        linked
+    }
+
+    /// The wiki links that could not be resolved or are ambiguous
+    pub fn into_issues(self) -> Vec<BrokenLink> {
+        self.issues
+    }
+
+    /// The index and url of the document (and section) for `target`. If there're
+    /// multiple documents, one in the language `lang` is preferred.
+    fn find(&self, target: &str, lang: &str) -> Result<(usize, String), String> {
+        let (name, section) = match target.split_once('#') {
+            Some((name, section)) => (name, Some(section)),
+            None => (target, None),
+        };
+        let mut candidates: Vec<usize> = self
+            .keys
+            .get(&normalized_key(name))
+            .cloned()
+            .unwrap_or_default();
+        if candidates.len() > 1 {
+            let same_language: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|index| self.targets[*index].lang == lang)
+                .collect();
+            if !same_language.is_empty() {
+                candidates = same_language;
+            }
+        }
+        let index = match candidates.as_slice() {
+            [index] => *index,
+            [] => return Err(format!("Could not resolve wiki link [[{}]]", target)),
+            _ => {
+                let slugs: Vec<&str> = candidates
+                    .iter()
+                    .map(|index| self.targets[*index].link.slug.as_str())
+                    .collect();
+                return Err(format!(
+                    "Ambiguous wiki link [[{}]] matches {}",
+                    target,
+                    slugs.join(", ")
+                ));
+            }
+        };
+        let document = &self.targets[index];
+        let section = match section {
+            Some(section) if !section.is_empty() => section,
+            _ => return Ok((index, document.link.slug.clone())),
+        };
+        let anchor = utils::anchor_slug(section);
+        let sections = Section::flattened(&document.sections);
+        match sections.iter().find(|s| {
+            s.anchor == section || s.anchor == anchor || utils::anchor_slug(&s.title) == anchor
+        }) {
+            Some(found) => Ok((index, format!("{}#{}", &document.link.slug, &found.anchor))),
+            None => Err(format!(
+                "Could not find section `{}` of {} for wiki link [[{}]]",
+                section, &document.link.slug, target
+            )),
+        }
+    }
+}
+
+/// Resolve the wiki links in all `documents` and set the `backlinks` of the
+/// documents they point to. Returns the `WikiLinks` to resolve the links of
+/// further documents (e.g. one page books) that can't be linked themselves.
+pub fn resolve_wiki_links(documents: &mut [&mut Document]) -> WikiLinks {
+    let mut wiki_links = WikiLinks::new(documents);
+    let mut backlinks: Vec<Vec<usize>> = vec![Vec::new(); documents.len()];
+    for (index, document) in documents.iter_mut().enumerate() {
+        for linked in wiki_links.resolve(document) {
+            if linked != index && !backlinks[linked].contains(&index) {
+                backlinks[linked].push(index);
+            }
+        }
+    }
+    for (document, sources) in documents.iter_mut().zip(backlinks) {
+        if sources
+            .iter()
+            .any(|index| wiki_links.targets[*index].updated)
+        {
+            document.updated = true;
+        }
+        document.backlinks = sources
+            .into_iter()
+            .map(|index| wiki_links.targets[index].link.clone())
+            .collect();
+    }
+    wiki_links
+}
+
+/// The names a document can be linked with: The title, the slug (with and
+/// without `.html`), the last part of the slug, the filename and the identifier
+fn document_keys(document: &Document) -> Vec<String> {
+    let slug = document.slug.trim_start_matches('/');
+    let mut keys = vec![
+        document.info.title.as_str(),
+        slug,
+        slug.trim_end_matches(".html"),
+        document.identifier.as_str(),
+    ];
+    if let Some(stem) = Path::new(slug).file_stem().and_then(|s| s.to_str()) {
+        keys.push(stem);
+    }
+    if let Some(stem) = Path::new(&document.filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+    {
+        keys.push(stem);
+    }
+    let mut keys: Vec<String> = keys
+        .into_iter()
+        .filter(|key| !key.is_empty())
+        .map(normalized_key)
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn normalized_key(key: &str) -> String {
+    key.trim().trim_start_matches('/').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::front_matter::parse_front_matter;
+
+    fn document(title: &str, slug: &str, content: &str) -> Document {
+        let config = Config::default();
+        let front_matter = format!(
+            "[frontMatter]\ntitle = \"{}\"\ncreated = \"2009-12-30\"\n---\n",
+            title
+        );
+        let (info, _) = parse_front_matter(&front_matter, "test.md", &config).unwrap();
+        let sections = vec![Section {
+            level: 2,
+            anchor: "first-steps".to_string(),
+            title: "First Steps".to_string(),
+            children: Vec::new(),
+        }];
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(
+            &mut html,
+            pulldown_cmark::Parser::new(&preprocess_wiki_links(content)),
+        );
+        Document::from_multiple(html, content, slug, "test.md", &info, sections, "en")
+    }
+
+    #[test]
+    fn test_preprocess_wiki_links() {
+        let markdown = "See [[My Post]] and [[other#intro|the intro]], not `[[code]]`\n\n```\n[[block]]\n```\n";
+        assert_eq!(
+            preprocess_wiki_links(markdown),
+            "See [My Post](<wiki::My Post>) and [the intro](<wiki::other#intro>), not `[[code]]`\n\n```\n[[block]]\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_wiki_links() {
+        let mut first = document("My Post", "/posts/my-post.html", "Hello");
+        let mut second = document(
+            "Other",
+            "books/book/chapter.html",
+            "[[My Post]] [[my-post#first steps]] [[Missing]]",
+        );
+        let mut third = document("Third", "/posts/third.html", "[[Other|a chapter]]");
+        let mut documents = vec![&mut first, &mut second, &mut third];
+        let issues = resolve_wiki_links(&mut documents).into_issues();
+        assert!(second
+            .content
+            .contains(r#"<a href="/posts/my-post.html">My Post</a>"#));
+        assert!(second
+            .content
+            .contains(r#"<a href="/posts/my-post.html#first-steps">my-post#first steps</a>"#));
+        assert!(second.content.contains(r#"href="wiki::Missing""#));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].to_string(),
+            "test.md:1: [[Missing]] (Could not resolve wiki link [[Missing]])"
+        );
+        assert_eq!(issues[0].file, PathBuf::from("books/book/chapter.html"));
+        assert!(third
+            .content
+            .contains(r#"<a href="/books/book/chapter.html">a chapter</a>"#));
+        assert_eq!(first.backlinks.len(), 1);
+        assert_eq!(first.backlinks[0].title, "Other");
+        assert_eq!(second.backlinks[0].title, "Third");
+    }
+
+    #[test]
+    fn test_ambiguous_wiki_links() {
+        let mut first = document("Same", "/posts/a.html", "");
+        let mut second = document("Same", "/posts/b.html", "");
+        let mut third = document("Third", "/posts/c.html", "[[Same]] [[a]]");
+        let mut documents = vec![&mut first, &mut second, &mut third];
+        let issues = resolve_wiki_links(&mut documents).into_issues();
+        assert!(third.content.contains(r#"href="wiki::Same""#));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].reason,
+            "Ambiguous wiki link [[Same]] matches /posts/a.html, /posts/b.html"
+        );
+        assert!(third.content.contains(r#"href="/posts/a.html""#));
+    }
+}